@@ -55,6 +55,11 @@ impl BoundingBox {
 
     /// Checks if the bounding box intersects a ray within max distance
     pub fn intersects_ray(&self, ray: &Ray, max: Distance) -> bool {
+        self.ray_entry(ray, max).is_some()
+    }
+
+    /// Distance at which a ray enters the bounding box, zero if it starts inside
+    pub fn ray_entry(&self, ray: &Ray, max: Distance) -> Option<Distance> {
         let mut t_near = Distance::NEG_INFINITY;
         let mut t_far = Distance::INFINITY;
 
@@ -71,12 +76,33 @@ impl BoundingBox {
 
             // Early exit: If t_near is greater than t_far, there's no intersection
             if t_near > t_far {
-                return false;
+                return None;
             }
         }
 
         // Ensure the intersection is in the positive ray direction
-        t_far >= 0.0 && t_near <= max
+        if t_far >= 0.0 && t_near <= max {
+            Some(t_near.max(0.0))
+        } else {
+            None
+        }
+    }
+
+    /// Surface area of the box, used by the surface area heuristic.
+    pub fn surface_area(&self) -> Distance {
+        let size = self.max - self.min;
+        if size.x < 0.0 || size.y < 0.0 || size.z < 0.0 {
+            return 0.0; // Empty
+        }
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    /// Grows the bounding box to contain a point.
+    pub fn include(&self, point: &Point3) -> Self {
+        Self {
+            min: self.min.min(point),
+            max: self.max.max(point),
+        }
     }
 
     /// Computes the center point of the bounding box.
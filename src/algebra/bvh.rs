@@ -0,0 +1,379 @@
+use crate::algebra::{BoundingBox, Distance, Point3, Ray};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Cost of visiting a node relative to testing one primitive.
+const TRAVERSAL_COST: Distance = 1.0;
+
+/// Deeper trees are cut off with leaves, which bounds the traversal stack.
+const MAX_DEPTH: usize = 60;
+
+#[derive(Debug, Copy, Clone)]
+pub struct BvhConfig {
+    pub max_leaf_size: usize,
+    pub buckets: usize,
+}
+
+impl BvhConfig {
+    pub const DEFAULT: BvhConfig = BvhConfig {
+        max_leaf_size: 4,
+        buckets: 12,
+    };
+
+    pub fn new(max_leaf_size: usize, buckets: usize) -> Self {
+        Self {
+            max_leaf_size: max_leaf_size.max(1),
+            buckets: buckets.max(2),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct BuildStats {
+    pub primitives: usize,
+    pub nodes: usize,
+    pub leaves: usize,
+    pub max_depth: usize,
+    pub max_leaf_size: usize,
+    /// Expected cost of a ray through the root, in primitive tests
+    pub cost: Distance,
+    pub build_time: Duration,
+}
+
+impl fmt::Display for BuildStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} primitives, {} nodes, {} leaves, depth {}, largest leaf {}, SAH cost {:.2}, built in {:?}",
+            self.primitives, self.nodes, self.leaves, self.max_depth, self.max_leaf_size, self.cost, self.build_time
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct TraversalStats {
+    pub rays: usize,
+    pub nodes_visited: usize,
+    pub primitives_tested: usize,
+}
+
+impl fmt::Display for TraversalStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rays = self.rays.max(1) as Distance;
+        write!(
+            f,
+            "{} rays, {:.1} nodes and {:.1} primitives per ray",
+            self.rays,
+            self.nodes_visited as Distance / rays,
+            self.primitives_tested as Distance / rays
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct BvhNode {
+    bounds: BoundingBox,
+    /// First primitive for leaves, second child for interior nodes (the first child follows the node)
+    offset: usize,
+    /// Number of primitives, zero for interior nodes
+    count: usize,
+    axis: usize,
+}
+
+/// Bounding volume hierarchy over primitives given by their bounding boxes, built with the
+/// surface area heuristic. The primitives themselves are tested through callbacks, by index.
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>,
+    stats: BuildStats,
+}
+
+struct Builder<'a> {
+    bounds: &'a [BoundingBox],
+    centroids: Vec<Point3>,
+    config: &'a BvhConfig,
+    nodes: Vec<BvhNode>,
+    stats: BuildStats,
+}
+
+#[derive(Copy, Clone)]
+struct Bucket {
+    count: usize,
+    bounds: BoundingBox,
+}
+
+impl Builder<'_> {
+    fn leaf(&mut self, node: usize, bounds: BoundingBox, offset: usize, count: usize, depth: usize) {
+        self.nodes[node] = BvhNode { bounds, offset, count, axis: 0 };
+        self.stats.leaves += 1;
+        self.stats.max_depth = self.stats.max_depth.max(depth);
+        self.stats.max_leaf_size = self.stats.max_leaf_size.max(count);
+    }
+
+    /// Builds the subtree for `indices`, which start at `offset` in the final index list.
+    fn build(&mut self, indices: &mut [usize], offset: usize, depth: usize) -> usize {
+        let node = self.nodes.len();
+        let bounds = indices.iter().fold(BoundingBox::empty(), |b, i| b.union(&self.bounds[*i]));
+        self.nodes.push(BvhNode { bounds, offset, count: indices.len(), axis: 0 });
+
+        let count = indices.len();
+        if count <= 1 || depth >= MAX_DEPTH {
+            self.leaf(node, bounds, offset, count, depth);
+            return node;
+        }
+
+        let centroid_bounds = indices.iter().fold(BoundingBox::empty(), |b, i| b.include(&self.centroids[*i]));
+        let extent = centroid_bounds.max - centroid_bounds.min;
+        let axis = extent.imax();
+
+        let mid = if extent[axis] <= 0.0 {
+            // All centroids coincide, the heuristic cannot separate them
+            if count <= self.config.max_leaf_size {
+                self.leaf(node, bounds, offset, count, depth);
+                return node;
+            }
+            count / 2
+        } else {
+            let buckets = self.config.buckets;
+            let bucket_of = |centroid: &Point3| {
+                let relative = (centroid[axis] - centroid_bounds.min[axis]) / extent[axis];
+                ((relative * buckets as Distance) as usize).min(buckets - 1)
+            };
+
+            let mut bins = vec![Bucket { count: 0, bounds: BoundingBox::empty() }; buckets];
+            for i in indices.iter() {
+                let bin = &mut bins[bucket_of(&self.centroids[*i])];
+                bin.count += 1;
+                bin.bounds = bin.bounds.union(&self.bounds[*i]);
+            }
+
+            // Cost of splitting after each bucket, from sweeps in both directions
+            let mut left_cost = vec![0.0; buckets];
+            let mut running = Bucket { count: 0, bounds: BoundingBox::empty() };
+            for (i, bin) in bins.iter().enumerate().take(buckets - 1) {
+                running = Bucket { count: running.count + bin.count, bounds: running.bounds.union(&bin.bounds) };
+                left_cost[i] = running.count as Distance * running.bounds.surface_area();
+            }
+
+            let mut best = (Distance::INFINITY, 0);
+            let mut running = Bucket { count: 0, bounds: BoundingBox::empty() };
+            for split in (1..buckets).rev() {
+                let bin = &bins[split];
+                running = Bucket { count: running.count + bin.count, bounds: running.bounds.union(&bin.bounds) };
+                let cost = left_cost[split - 1] + running.count as Distance * running.bounds.surface_area();
+                if cost < best.0 {
+                    best = (cost, split);
+                }
+            }
+
+            let area = bounds.surface_area();
+            let split_cost = TRAVERSAL_COST + if area > 0.0 { best.0 / area } else { count as Distance };
+            if count <= self.config.max_leaf_size && split_cost >= count as Distance {
+                self.leaf(node, bounds, offset, count, depth);
+                return node;
+            }
+
+            let mut mid = 0;
+            for i in 0..count {
+                if bucket_of(&self.centroids[indices[i]]) < best.1 {
+                    indices.swap(i, mid);
+                    mid += 1;
+                }
+            }
+
+            if mid == 0 || mid == count { count / 2 } else { mid }
+        };
+
+        let (left, right) = indices.split_at_mut(mid);
+        self.build(left, offset, depth + 1);
+        let second = self.build(right, offset + mid, depth + 1);
+        self.nodes[node] = BvhNode { bounds, offset: second, count: 0, axis };
+
+        node
+    }
+}
+
+impl Bvh {
+    pub fn build(bounds: &[BoundingBox], config: &BvhConfig) -> Self {
+        let start = Instant::now();
+
+        let mut indices: Vec<usize> = (0..bounds.len()).collect();
+        let mut builder = Builder {
+            bounds,
+            centroids: bounds.iter().map(|b| b.center()).collect(),
+            config,
+            nodes: Vec::with_capacity(2 * bounds.len()),
+            stats: BuildStats { primitives: bounds.len(), ..BuildStats::default() },
+        };
+
+        if !bounds.is_empty() {
+            builder.build(&mut indices, 0, 0);
+        }
+
+        let nodes = builder.nodes;
+        let mut stats = builder.stats;
+        stats.nodes = nodes.len();
+        stats.cost = Self::cost(&nodes);
+        stats.build_time = start.elapsed();
+
+        Self { nodes, indices, stats }
+    }
+
+    /// Expected number of primitive tests for a ray hitting the root, by the surface area heuristic.
+    fn cost(nodes: &[BvhNode]) -> Distance {
+        let Some(root) = nodes.first() else {
+            return 0.0;
+        };
+        let root_area = root.bounds.surface_area();
+        if root_area <= 0.0 {
+            return root.count as Distance;
+        }
+
+        nodes
+            .iter()
+            .map(|n| {
+                let probability = n.bounds.surface_area() / root_area;
+                if n.count == 0 { TRAVERSAL_COST * probability } else { n.count as Distance * probability }
+            })
+            .sum()
+    }
+
+    pub fn bounding_box(&self) -> BoundingBox {
+        self.nodes.first().map_or(BoundingBox::empty(), |n| n.bounds)
+    }
+
+    pub fn build_stats(&self) -> &BuildStats {
+        &self.stats
+    }
+
+    /// Finds the closest primitive hit. `intersect` is called with a primitive index and the
+    /// current maximum distance, and returns the distance and result of a hit.
+    pub fn closest<T>(&self, ray: &Ray, max: Distance, intersect: impl FnMut(usize, Distance) -> Option<(Distance, T)>) -> Option<T> {
+        self.closest_counted(ray, max, &mut TraversalStats::default(), intersect)
+    }
+
+    /// Like `closest`, counting visited nodes and tested primitives in `stats`.
+    pub fn closest_counted<T>(
+        &self,
+        ray: &Ray,
+        max: Distance,
+        stats: &mut TraversalStats,
+        mut intersect: impl FnMut(usize, Distance) -> Option<(Distance, T)>,
+    ) -> Option<T> {
+        stats.rays += 1;
+        let mut result = None;
+        let mut shortest = max;
+
+        self.traverse(ray, max, stats, |index, stats| {
+            stats.primitives_tested += 1;
+            if let Some((distance, hit)) = intersect(index, shortest) {
+                shortest = distance;
+                result = Some(hit);
+            }
+            (shortest, false)
+        });
+
+        result
+    }
+
+    /// Checks whether any primitive is hit, stopping at the first one.
+    pub fn any(&self, ray: &Ray, max: Distance, intersects: impl FnMut(usize, Distance) -> bool) -> bool {
+        self.any_counted(ray, max, &mut TraversalStats::default(), intersects)
+    }
+
+    /// Like `any`, counting visited nodes and tested primitives in `stats`.
+    #[allow(dead_code)]
+    pub fn any_counted(
+        &self,
+        ray: &Ray,
+        max: Distance,
+        stats: &mut TraversalStats,
+        mut intersects: impl FnMut(usize, Distance) -> bool,
+    ) -> bool {
+        stats.rays += 1;
+        let mut found = false;
+
+        self.traverse(ray, max, stats, |index, stats| {
+            stats.primitives_tested += 1;
+            found = intersects(index, max);
+            (max, found)
+        });
+
+        found
+    }
+
+    /// Visits leaf primitives front to back. `visit` returns the new maximum distance and
+    /// whether to stop.
+    fn traverse(&self, ray: &Ray, mut max: Distance, stats: &mut TraversalStats, mut visit: impl FnMut(usize, &mut TraversalStats) -> (Distance, bool)) {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let mut stack = [0usize; MAX_DEPTH + 4];
+        let mut top = 1;
+
+        while top > 0 {
+            top -= 1;
+            let node = &self.nodes[stack[top]];
+            stats.nodes_visited += 1;
+
+            if node.bounds.ray_entry(ray, max).is_none() {
+                continue;
+            }
+
+            if node.count > 0 {
+                for index in &self.indices[node.offset..node.offset + node.count] {
+                    let (new_max, stop) = visit(*index, stats);
+                    if stop {
+                        return;
+                    }
+                    max = new_max;
+                }
+            } else {
+                // Push the far child first so the near child is visited first
+                let first = stack[top] + 1;
+                let (near, far) = if ray.direction[node.axis] < 0.0 { (node.offset, first) } else { (first, node.offset) };
+                stack[top] = far;
+                stack[top + 1] = near;
+                top += 2;
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Bvh {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Bvh {{ {} }}", self.stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::Vector3;
+
+    fn unit_box(center: Point3) -> BoundingBox {
+        BoundingBox::new(center - Vector3::new(0.5, 0.5, 0.5), center + Vector3::new(0.5, 0.5, 0.5))
+    }
+
+    #[test]
+    fn closest_matches_brute_force() {
+        let boxes: Vec<BoundingBox> = (0..1000)
+            .map(|i| unit_box(Point3::new((i % 10) as Distance * 2.0, ((i / 10) % 10) as Distance * 2.0, (i / 100) as Distance * 2.0)))
+            .collect();
+        let bvh = Bvh::build(&boxes, &BvhConfig::DEFAULT);
+        assert_eq!(bvh.build_stats().primitives, 1000);
+
+        let hit = |i: usize, max: Distance, ray: &Ray| boxes[i].ray_entry(ray, max).filter(|d| *d < max).map(|d| (d, d));
+
+        for x in 0..10 {
+            let ray = Ray::normalized(Point3::new(x as Distance * 2.0, -10.0, -10.0), Vector3::new(0.1, 1.0, 1.0));
+            let mut stats = TraversalStats::default();
+            let found = bvh.closest_counted(&ray, Distance::INFINITY, &mut stats, |i, max| hit(i, max, &ray));
+            let expected = (0..boxes.len()).filter_map(|i| hit(i, Distance::INFINITY, &ray)).map(|h| h.0).reduce(Distance::min);
+
+            assert_eq!(found, expected);
+            assert!(stats.primitives_tested < boxes.len());
+        }
+    }
+}
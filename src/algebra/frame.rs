@@ -180,10 +180,11 @@ impl Frame {
                     origin.coords.dot(&inv_z),
                 );
 
+                // The scaled axes are the rows of the inverse, transpose them into columns
                 Frame::General {
-                    x_axis: inv_x,
-                    y_axis: inv_y,
-                    z_axis: inv_z,
+                    x_axis: Vector3::new(inv_x.x, inv_y.x, inv_z.x),
+                    y_axis: Vector3::new(inv_x.y, inv_y.y, inv_z.y),
+                    z_axis: Vector3::new(inv_x.z, inv_y.z, inv_z.z),
                     origin: inv_origin,
                 }
             }
@@ -226,3 +227,26 @@ impl Frame {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Points taken to world space and back are unchanged, with axes that are rotated as well
+    /// as scaled.
+    #[test]
+    fn inverse_undoes_general_frame() {
+        let frame = Frame::General {
+            x_axis: Vector3::new(0.0, 2.0, 0.0),
+            y_axis: Vector3::new(-3.0, 0.0, 0.0),
+            z_axis: Vector3::new(0.0, 0.0, 0.5),
+            origin: Point3::new(1.0, 2.0, 3.0),
+        };
+        let point = Point3::new(0.3, -1.2, 4.0);
+
+        let back = frame.inverse().transform_point(&frame.transform_point(&point));
+        assert!((back - point).magnitude() < 1e-9, "{:?}", back);
+        let back = frame.transform_point(&frame.inverse().transform_point(&point));
+        assert!((back - point).magnitude() < 1e-9, "{:?}", back);
+    }
+}
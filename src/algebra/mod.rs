@@ -1,10 +1,12 @@
 mod bounding;
 mod ray;
 mod frame;
+mod bvh;
 
 pub use frame::Frame;
 pub use ray::Ray;
 pub use bounding::{BoundingBox, Bounded};
+pub use bvh::{Bvh, BvhConfig, BuildStats};
 use nalgebra::{Unit, Vector3 as NVector3};
 use nalgebra::Matrix4 as NMatrix4;
 use nalgebra::Point3 as NPoint3;
@@ -34,7 +36,6 @@ impl DistanceConstants<f64> for f64 {
 }
 
 pub trait VectorOps {
-    #[allow(dead_code)]
     fn perpendicular(&self) -> Vector3;
 
     fn cos_angle(&self, v: &Vector3) -> Distance;
@@ -57,6 +58,30 @@ impl VectorOps for Vector3 {
     }
 }
 
+/// Direction around the normal with a probability proportional to the cosine of its angle,
+/// chosen by `u` in the unit square.
+pub fn cosine_direction(normal: &UnitVector3, u: (Distance, Distance)) -> Vector3 {
+    let radius = u.0.sqrt();
+    let phi = 2.0 * Distance::PI * u.1;
+
+    let tangent = normal.perpendicular();
+    let bitangent = normal.cross(&tangent);
+    tangent * (radius * phi.cos()) + bitangent * (radius * phi.sin()) + normal.into_inner() * (1.0 - u.0).sqrt()
+}
+
+/// Position in the `index`th of `count` cells of equal area covering the unit square, at `jitter`
+/// within the cell. Cells are laid out in rows of about the square root of `count`, the last row
+/// taller and with fewer wider cells if `count` is not a square.
+pub fn stratum(index: u32, count: u32, jitter: (Distance, Distance)) -> (Distance, Distance) {
+    let columns = (count as Distance).sqrt().ceil() as u32;
+    let (row, column) = (index / columns, index % columns);
+    let cells = columns.min(count - row * columns);
+    (
+        (column as Distance + jitter.0) / cells as Distance,
+        ((row * columns) as Distance + jitter.1 * cells as Distance) / count as Distance,
+    )
+}
+
 pub trait Point3Ops {
     fn min(&self, other: &Self) -> Self;
     fn max(&self, other: &Self) -> Self;
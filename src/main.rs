@@ -1,18 +1,20 @@
 use crate::algebra::Ray;
 use crate::algebra::{Distance, Point3, Vector3};
 use crate::buffer::BufferedChannel;
-use crate::render::{RenderListener, Renderer, TraceRenderConfig, TraceRenderer};
+use crate::render::{AcceleratorConfig, BvhConfig, Filter, IntegratorConfig, OctreeConfig, PathConfig, RenderListener, Renderer, SamplePattern, SamplingConfig, ToneMapOperator, ToneMapping, TraceRenderConfig, TraceRenderer, Transfer};
 use crate::scene::light::Light;
 use crate::scene::object::Object;
 use crate::scene::surface::Surface;
-use crate::scene::Scene;
+use crate::scene::viewpoint::{Lens, ProjectionKind, Viewpoint};
+use crate::scene::{ColorPart, Scene};
 use crate::visualize::show;
 use crate::visualize::ShowMessage::{ShowImage, ShowPixelMessage};
-use clap::Parser;
-use image::{Rgb, RgbImage};
+use clap::{Args, Parser, ValueEnum};
+use image::{Rgb, Rgb32FImage};
 use nalgebra::min;
 use scene::texture::CheckerboardTexture;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use crate::scene::material::Material;
 
 mod algebra;
@@ -57,14 +59,188 @@ struct Cli {
     #[arg(long, default_value_t = false)]
     video: bool,
 
-    /// Path to save the generated image (ignored in visualization or video mode)
+    /// Path to save the generated image (ignored in visualization or video mode).
+    /// Extensions .exr, .hdr and .pfm keep the linear floating point colors
     #[arg(short, long, value_name = "FILE")]
     output: Option<PathBuf>,
+
+    /// Scene description file (TOML), replaces the built-in scene
+    #[arg(long, value_name = "FILE")]
+    scene: Option<PathBuf>,
+
+    /// Acceleration structure for finding the objects hit by rays
+    #[arg(long, value_enum, default_value_t = Accelerator::Bvh)]
+    accelerator: Accelerator,
+
+    /// Objects in an octree node before it is subdivided
+    #[arg(long, default_value_t = OctreeConfig::DEFAULT.max_objects)]
+    octree_max_objects: usize,
+
+    /// Maximum depth of the octree
+    #[arg(long, default_value_t = OctreeConfig::DEFAULT.max_depth)]
+    octree_max_depth: usize,
+
+    /// How much octree nodes are enlarged when deciding if an object fits in them
+    #[arg(long, default_value_t = OctreeConfig::DEFAULT.loose_factor)]
+    octree_loose_factor: Distance,
+
+    /// Largest number of objects in a BVH leaf
+    #[arg(long, default_value_t = BvhConfig::DEFAULT.max_leaf_size)]
+    bvh_max_leaf_size: usize,
+
+    /// Number of buckets evaluated by the surface area heuristic when splitting BVH nodes
+    #[arg(long, default_value_t = BvhConfig::DEFAULT.buckets)]
+    bvh_buckets: usize,
+
+    /// How the light reaching the camera is computed
+    #[arg(long, value_enum, default_value_t = Integrator::Whitted)]
+    integrator: Integrator,
+
+    /// Longest path followed by the path tracer
+    #[arg(long, default_value_t = PathConfig::DEFAULT.max_bounces)]
+    max_bounces: u32,
+
+    /// Bounces before the path tracer may end paths by Russian roulette
+    #[arg(long, default_value_t = PathConfig::DEFAULT.roulette_bounces)]
+    roulette_bounces: u32,
+
+    /// Samples per pixel
+    #[arg(long, default_value_t = SamplingConfig::DEFAULT.samples_per_pixel)]
+    spp: u32,
+
+    /// How samples are placed within pixels
+    #[arg(long, value_enum, default_value_t = SamplingConfig::DEFAULT.pattern)]
+    sampling: SamplePattern,
+
+    /// Reconstruction filter combining the samples of a pixel
+    #[arg(long, value_enum, default_value_t = SamplingConfig::DEFAULT.filter)]
+    filter: Filter,
+
+    /// Tone mapping operator for 8 bit images and the visualization
+    #[arg(long, value_enum, default_value_t = ToneMapping::DEFAULT.operator)]
+    tone_map: ToneMapOperator,
+
+    /// Exposure adjustment in stops, applied before tone mapping
+    #[arg(long, default_value_t = ToneMapping::DEFAULT.exposure, allow_negative_numbers = true)]
+    exposure: ColorPart,
+
+    /// Encoding of tone mapped values
+    #[arg(long, value_enum, default_value_t = ToneMapping::DEFAULT.transfer)]
+    transfer: Transfer,
+
+    #[command(flatten)]
+    camera: CameraArgs,
+}
+
+/// Overrides of the camera defined by the scene
+#[derive(Args, Debug, Copy, Clone)]
+struct CameraArgs {
+    /// Camera position
+    #[arg(long = "camera-position", value_name = "X,Y,Z", value_parser = parse_triple)]
+    position: Option<[Distance; 3]>,
+
+    /// Point the camera looks at
+    #[arg(long = "camera-target", value_name = "X,Y,Z", value_parser = parse_triple)]
+    target: Option<[Distance; 3]>,
+
+    /// Up direction of the camera
+    #[arg(long = "camera-up", value_name = "X,Y,Z", value_parser = parse_triple)]
+    up: Option<[Distance; 3]>,
+
+    /// Vertical field of view in degrees
+    #[arg(long)]
+    fov: Option<Distance>,
+
+    /// Lens radius for depth of field, zero keeps everything in focus
+    #[arg(long)]
+    aperture: Option<Distance>,
+
+    /// Distance to the plane in focus, defaults to the distance to the camera target
+    #[arg(long)]
+    focal_distance: Option<Distance>,
+
+    /// Number of aperture blades for polygonal bokeh, zero for a circular aperture
+    #[arg(long)]
+    blades: Option<u32>,
+
+    /// Camera projection
+    #[arg(long, value_enum)]
+    projection: Option<ProjectionKind>,
+}
+
+impl CameraArgs {
+    fn apply(&self, viewpoint: &Viewpoint) -> Viewpoint {
+        Viewpoint {
+            position: self.position.map(Point3::from).unwrap_or(viewpoint.position),
+            target: self.target.map(Point3::from).unwrap_or(viewpoint.target),
+            up: self.up.map(Vector3::from).unwrap_or(viewpoint.up),
+            fov: self.fov.unwrap_or(viewpoint.fov),
+            lens: Lens {
+                aperture: self.aperture.unwrap_or(viewpoint.lens.aperture),
+                focal_distance: self.focal_distance.or(viewpoint.lens.focal_distance),
+                blades: self.blades.unwrap_or(viewpoint.lens.blades),
+            },
+            projection: self.projection.unwrap_or(viewpoint.projection),
+        }
+    }
 }
-fn generate_image(scene: &Scene, width: u32, height: u32, tx: impl RenderListener, parallel: bool) -> RgbImage {
-    let renderer = TraceRenderer::new(TraceRenderConfig {
-        parallel
-    });
+
+fn parse_triple(s: &str) -> Result<[Distance; 3], String> {
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse::<Distance>().map_err(|e| format!("invalid number \"{}\": {}", v, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    values.try_into().map_err(|_| "expected three comma separated numbers".to_string())
+}
+
+#[derive(ValueEnum, Debug, Copy, Clone)]
+enum Accelerator {
+    Flat,
+    Octree,
+    Bvh,
+}
+
+#[derive(ValueEnum, Debug, Copy, Clone)]
+enum Integrator {
+    /// Mirror and refraction rays with direct light only
+    Whitted,
+    /// Monte Carlo path tracing with indirect light, needs many samples per pixel
+    Path,
+}
+
+impl Cli {
+    fn render_config(&self) -> TraceRenderConfig {
+        TraceRenderConfig {
+            parallel: self.parallel,
+            accelerator: match self.accelerator {
+                Accelerator::Flat => AcceleratorConfig::Flat,
+                Accelerator::Octree => AcceleratorConfig::Octree(OctreeConfig::new(self.octree_max_objects, self.octree_max_depth, self.octree_loose_factor)),
+                Accelerator::Bvh => AcceleratorConfig::Bvh(BvhConfig::new(self.bvh_max_leaf_size, self.bvh_buckets)),
+            },
+            sampling: SamplingConfig {
+                samples_per_pixel: self.spp,
+                pattern: self.sampling,
+                filter: self.filter,
+            },
+            integrator: match self.integrator {
+                Integrator::Whitted => IntegratorConfig::Whitted,
+                Integrator::Path => IntegratorConfig::Path(PathConfig::new(self.max_bounces, self.roulette_bounces)),
+            },
+        }
+    }
+
+    fn tone_mapping(&self) -> ToneMapping {
+        ToneMapping {
+            operator: self.tone_map,
+            exposure: self.exposure,
+            transfer: self.transfer,
+        }
+    }
+}
+
+fn generate_image(scene: &Scene, width: u32, height: u32, tx: impl RenderListener, config: TraceRenderConfig) -> Rgb32FImage {
+    let renderer = TraceRenderer::new(config);
     
     renderer.render(scene, width, height, tx)
 }
@@ -73,6 +249,13 @@ fn create_scene(frame: u32) -> Scene {
     create_scene2(frame)
 }
 
+fn load_scene(path: &Path) -> Scene {
+    scene::loader::load(path).unwrap_or_else(|e| {
+        eprintln!("Failed to load scene: {}", e);
+        std::process::exit(1)
+    })
+}
+
 #[allow(dead_code)]
 fn create_scene1(_frame: u32) -> Scene {
     const MAT: Material = Material::DEFAULT;
@@ -180,31 +363,48 @@ fn create_scene4<'a>(_frame: u32) -> Scene {
 
 fn main() {
     let cli = Cli::parse();
+    let config = cli.render_config();
+    let tone_mapping = cli.tone_mapping();
+
+    let camera = cli.camera;
+    let with_camera = move |mut scene: Scene| {
+        let viewpoint = camera.apply(scene.viewpoint());
+        if let Err(message) = viewpoint.check() {
+            eprintln!("Invalid camera: {}", message);
+            std::process::exit(1)
+        }
+        scene.set_viewpoint(viewpoint);
+        scene
+    };
+
+    // A scene file is static, the built-in scene is animated by frame
+    let file_scene = cli.scene.as_ref().map(|path| Arc::new(with_camera(load_scene(path))));
+    let scene_at = move |frame: u32| file_scene.clone().unwrap_or_else(|| Arc::new(with_camera(create_scene(frame))));
 
     if cli.visualize {
         show(cli.width as i32, cli.height as i32, move |tx| {
             if cli.video {
                 let btx = BufferedChannel::new(min(cli.video_frames as usize, cli.video_buffer as usize), 4.0, move |m|tx.send_blocking(m).unwrap());
                 for i in 0..cli.video_frames {
-                    let scene = create_scene(i);
-                    let image = generate_image(&scene, cli.width, cli.height, |_m| {}, cli.parallel);
+                    let scene = scene_at(i);
+                    let image = generate_image(&scene, cli.width, cli.height, |_m| {}, config);
 
-                    btx.send(ShowImage(image)).unwrap()
+                    btx.send(ShowImage(tone_mapping.apply(&image))).unwrap()
                 }
             } else {
-                let scene = create_scene(0);
-                generate_image(&scene, cli.width, cli.height, |(x, y, c)|tx.send_blocking(ShowPixelMessage(x, y, c)).unwrap(), cli.parallel);
+                let scene = scene_at(0);
+                generate_image(&scene, cli.width, cli.height, |(x, y, c)|tx.send_blocking(ShowPixelMessage(x, y, tone_mapping.map(c))).unwrap(), config);
             }
         })
     } else {
-        let scene = create_scene(0);
+        let scene = scene_at(0);
         let image = (0..cli.bench.max(1))
-            .map(|_| generate_image(&scene, cli.width, cli.height, |_m| {}, cli.parallel))
+            .map(|_| generate_image(&scene, cli.width, cli.height, |_m| {}, config))
             .last()
             .unwrap();
         
         let output_path = cli.output.unwrap_or(PathBuf::from("output.png"));
 
-        image.save(output_path).expect("Failed to save image");
+        render::output::save(&image, &output_path, &tone_mapping).expect("Failed to save image");
     }
 }
\ No newline at end of file
@@ -1,12 +1,18 @@
 mod trace;
+pub mod output;
+mod tonemap;
 
-use image::{Rgb, RgbImage};
-use crate::scene::Scene;
+use image::Rgb32FImage;
+use crate::scene::{Color, Scene};
 pub use trace::{TraceRenderer, TraceRenderConfig};
+pub use trace::world::{AcceleratorConfig, BvhConfig, IntegratorConfig, OctreeConfig, PathConfig};
+pub use trace::camera::{Filter, SamplePattern, SamplingConfig};
+pub use tonemap::{ToneMapOperator, ToneMapping, Transfer};
 
-pub trait RenderListener: Fn((u32, u32, Rgb<u8>)) + Send + Sync {}
-impl<T> RenderListener for T where T: Fn((u32, u32, Rgb<u8>)) + Send + Sync {}
+/// Receives each pixel as soon as it is rendered, in linear color.
+pub trait RenderListener: Fn((u32, u32, Color)) + Send + Sync {}
+impl<T> RenderListener for T where T: Fn((u32, u32, Color)) + Send + Sync {}
 
 pub trait Renderer {
-    fn render(&self, scene: &Scene, width: u32, height: u32, tx: impl RenderListener) -> RgbImage;
+    fn render(&self, scene: &Scene, width: u32, height: u32, tx: impl RenderListener) -> Rgb32FImage;
 }
@@ -0,0 +1,75 @@
+use crate::render::tonemap::ToneMapping;
+use image::{ImageError, ImageResult, Rgb32FImage};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// File format of the saved image, chosen by the file extension.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum OutputFormat {
+    /// Linear floating point formats keeping the full dynamic range of the render
+    OpenExr,
+    Radiance,
+    Pfm,
+    /// Any 8 bit format known to the image crate, from the extension
+    Ldr,
+}
+
+impl OutputFormat {
+    fn from_path(path: &Path) -> Self {
+        let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("exr") => OutputFormat::OpenExr,
+            Some("hdr") => OutputFormat::Radiance,
+            Some("pfm") => OutputFormat::Pfm,
+            _ => OutputFormat::Ldr,
+        }
+    }
+}
+
+/// Portable float map, three little endian floats per pixel with rows from bottom to top.
+fn write_pfm(image: &Rgb32FImage, path: &Path) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    // A negative scale marks little endian data
+    write!(writer, "PF\n{} {}\n-1.0\n", image.width(), image.height())?;
+    for row in image.rows().rev() {
+        for pixel in row {
+            for c in pixel.0 {
+                writer.write_all(&c.to_le_bytes())?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+/// Saves the framebuffer in the format chosen by the file extension. OpenEXR, Radiance HDR
+/// and PFM keep linear floating point values, other formats get an 8 bit copy made with `tone_mapping`.
+pub fn save(image: &Rgb32FImage, path: &Path, tone_mapping: &ToneMapping) -> ImageResult<()> {
+    match OutputFormat::from_path(path) {
+        OutputFormat::OpenExr | OutputFormat::Radiance => image.save(path),
+        OutputFormat::Pfm => write_pfm(image, path).map_err(ImageError::IoError),
+        OutputFormat::Ldr => tone_mapping.apply(image).save(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn writes_pfm_bottom_up() {
+        let image = Rgb32FImage::from_fn(2, 2, |_x, y| Rgb([y as f32 + 0.5, 2.0, 3.0]));
+        let path = std::env::temp_dir().join("rustray_writes_pfm_bottom_up.pfm");
+        save(&image, &path, &ToneMapping::DEFAULT).unwrap();
+
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let header = b"PF\n2 2\n-1.0\n";
+        assert_eq!(&data[..header.len()], header);
+        assert_eq!(data.len(), header.len() + 2 * 2 * 3 * 4);
+        let first = f32::from_le_bytes(data[header.len()..header.len() + 4].try_into().unwrap());
+        assert_eq!(first, 1.5);
+    }
+}
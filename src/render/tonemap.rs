@@ -0,0 +1,94 @@
+use crate::scene::{Color, ColorPart};
+use clap::ValueEnum;
+use image::{Rgb, Rgb32FImage, RgbImage};
+
+/// Compresses linear colors of any brightness into the displayable range.
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq)]
+pub enum ToneMapOperator {
+    /// Cuts off everything brighter than white
+    Clamp,
+    /// Reinhard on luminance, bright areas approach white keeping their hue
+    Reinhard,
+    /// Filmic curve fitted to the ACES reference rendering transform
+    Aces,
+}
+
+/// How tone mapped values are encoded in 8 bits.
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq)]
+pub enum Transfer {
+    /// sRGB transfer function, as expected by displays and image viewers
+    Srgb,
+    /// Linear values, as written before tone mapping was added
+    Linear,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ToneMapping {
+    pub operator: ToneMapOperator,
+    pub exposure: ColorPart, // In stops, each one doubles the brightness
+    pub transfer: Transfer,
+}
+
+impl ToneMapping {
+    pub const DEFAULT: ToneMapping = ToneMapping {
+        operator: ToneMapOperator::Clamp,
+        exposure: 0.0,
+        transfer: Transfer::Srgb,
+    };
+
+    fn tone_map(&self, color: [ColorPart; 3]) -> [ColorPart; 3] {
+        match self.operator {
+            ToneMapOperator::Clamp => color,
+            ToneMapOperator::Reinhard => {
+                let [r, g, b] = color;
+                let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+                if luminance <= 0.0 {
+                    return [0.0; 3];
+                }
+                color.map(|c| c / (1.0 + luminance))
+            }
+            ToneMapOperator::Aces => {
+                // Krzysztof Narkowicz's fit
+                color.map(|x| (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14))
+            }
+        }
+    }
+
+    fn encode(&self, c: ColorPart) -> ColorPart {
+        match self.transfer {
+            Transfer::Srgb if c <= 0.0031308 => c * 12.92,
+            Transfer::Srgb => 1.055 * c.powf(1.0 / 2.4) - 0.055,
+            Transfer::Linear => c,
+        }
+    }
+
+    /// Converts a linear color to 8 bits for display.
+    pub fn map(&self, color: Color) -> Rgb<u8> {
+        let scale = self.exposure.exp2();
+        let Rgb(c) = color;
+        let mapped = self.tone_map(c.map(|c| (c * scale).max(0.0)));
+        Rgb(mapped.map(|c| (self.encode(c.clamp(0.0, 1.0)) * 255.0).round() as u8))
+    }
+
+    pub fn apply(&self, image: &Rgb32FImage) -> RgbImage {
+        RgbImage::from_fn(image.width(), image.height(), |x, y| self.map(*image.get_pixel(x, y)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_black_and_white() {
+        for operator in [ToneMapOperator::Clamp, ToneMapOperator::Reinhard, ToneMapOperator::Aces] {
+            let mapping = ToneMapping { operator, ..ToneMapping::DEFAULT };
+            assert_eq!(mapping.map(Rgb([0.0; 3])), Rgb([0; 3]), "{:?}", operator);
+            assert_eq!(mapping.map(Rgb([1000.0; 3])).0.map(|c| c >= 254), [true; 3], "{:?}", operator);
+        }
+
+        // Middle gray is encoded well above half its linear value
+        let Rgb([gray, _, _]) = ToneMapping::DEFAULT.map(Rgb([0.18; 3]));
+        assert_eq!(gray, 118);
+    }
+}
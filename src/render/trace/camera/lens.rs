@@ -0,0 +1,68 @@
+use crate::algebra::{Distance, DistanceConstants};
+
+/// Shape of the lens opening, mapping sample positions onto it.
+#[derive(Debug, Copy, Clone)]
+pub struct Aperture {
+    radius: Distance,
+    blades: u32,
+}
+
+impl Aperture {
+    pub fn new(radius: Distance, blades: u32) -> Self {
+        Self { radius, blades }
+    }
+
+    pub fn is_pinhole(&self) -> bool {
+        self.radius <= 0.0
+    }
+
+    /// Maps a position in the unit square to an offset on the lens, along the camera's right and up axes.
+    pub fn sample(&self, position: (Distance, Distance)) -> (Distance, Distance) {
+        let (u, v) = position;
+
+        if self.blades < 3 {
+            // Concentric mapping keeps the strata of the samples on the disk
+            let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+            if a == 0.0 && b == 0.0 {
+                return (0.0, 0.0);
+            }
+            let (r, theta) = if a.abs() > b.abs() {
+                (a, Distance::PI / 4.0 * (b / a))
+            } else {
+                (b, Distance::PI / 2.0 - Distance::PI / 4.0 * (a / b))
+            };
+            return (self.radius * r * theta.cos(), self.radius * r * theta.sin());
+        }
+
+        // Pick one of the triangles between the center and two neighbouring corners,
+        // then a uniform position within it
+        let blades = self.blades as Distance;
+        let sector = (u * blades).floor().min(blades - 1.0);
+        let u = u * blades - sector;
+        let corner = |i: Distance| {
+            let angle = 2.0 * Distance::PI * i / blades;
+            (angle.cos() * self.radius, angle.sin() * self.radius)
+        };
+        let (c1, c2) = (corner(sector), corner(sector + 1.0));
+
+        let su = u.sqrt();
+        let (w1, w2) = (su * (1.0 - v), su * v);
+        (c1.0 * w1 + c2.0 * w2, c1.1 * w1 + c2.1 * w2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_within_aperture() {
+        for blades in [0, 5, 6] {
+            let aperture = Aperture::new(2.0, blades);
+            for i in 0..100 {
+                let (x, y) = aperture.sample(((i % 10) as Distance / 10.0, (i / 10) as Distance / 10.0));
+                assert!((x * x + y * y).sqrt() <= 2.0 + 1e-9);
+            }
+        }
+    }
+}
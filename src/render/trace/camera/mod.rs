@@ -1,80 +1,150 @@
 extern crate image;
 
-use crate::algebra::{Distance, Vector3};
-use crate::scene::ray::RayCaster;
+use crate::algebra::{Distance, Point3, Vector3, VectorOps};
+use crate::scene::ray::{RayCaster, MAX_DEPTH};
+use crate::scene::viewpoint::Viewpoint;
+use crate::scene::{Color, ColorPart};
 use crate::algebra::Ray;
-use image::{Rgb, RgbImage};
+use image::{Rgb, Rgb32FImage};
+pub use sampling::{Filter, SamplePattern, SamplingConfig};
+use sampling::{pixel_rng, FilterSampler};
+use lens::Aperture;
+use projection::{projection, Projection};
+
+mod sampling;
+mod lens;
+mod projection;
 
 pub struct Camera {
-    base: Ray,
+    position: Point3,
     width: u32,
     height: u32,
-    pixel_step_x: Vector3,
-    pixel_step_y: Vector3,
-    corner: Vector3, // Precomputed top-left corner of the camera plane
+    right: Vector3,
+    up: Vector3,
+    forward: Vector3,
+    projection: Box<dyn Projection>,
+    aperture: Aperture,
+    focal_distance: Distance,
+    sampling: SamplingConfig,
+    filter: FilterSampler,
 }
 
 impl Camera {
 
-    pub fn new(base: Ray, width: u32, height: u32, fov: Distance) -> Self {
+    pub fn new(viewpoint: &Viewpoint, width: u32, height: u32, sampling: SamplingConfig) -> Self {
         let aspect_ratio = width as Distance / height as Distance;
-        let fov_radians = (fov.to_radians()) / 2.0;
-        let scale = fov_radians.tan();
 
         // Compute camera basis vectors
-        let forward = base.direction.normalize();
-        let right = Vector3::new(0.0, 1.0, 0.0).cross(&forward).normalize(); // Assuming up = (0, 1, 0)
+        let forward = viewpoint.direction().normalize();
+        let right = viewpoint.up.cross(&forward);
+        let right = if right.magnitude() < 1e-9 {
+            // Looking along the up vector, any horizontal direction will do
+            forward.perpendicular()
+        } else {
+            right.normalize()
+        };
         let up = forward.cross(&right).normalize();
 
-        // Calculate pixel step sizes
-        let pixel_width = 2.0 * aspect_ratio * scale / width as Distance;
-        let pixel_height = 2.0 * scale / height as Distance;
-
-        // Precompute pixel step vectors
-        let pixel_step_x = right * pixel_width;
-        let pixel_step_y = -up * pixel_height; // Negative because y-axis is flipped in screen space
-
-        // Compute the top-left corner of the camera plane
-        let half_width = width as Distance / 2.0;
-        let half_height = height as Distance / 2.0;
-        let corner = forward
-            - pixel_step_x * half_width
-            - pixel_step_y * half_height;
-
         Self {
-            base,
+            position: viewpoint.position,
             width,
             height,
-            pixel_step_x,
-            pixel_step_y,
-            corner,
+            right,
+            up,
+            forward,
+            projection: projection(viewpoint, aspect_ratio),
+            aperture: Aperture::new(viewpoint.lens.aperture, viewpoint.lens.blades),
+            focal_distance: viewpoint.focal_distance(),
+            sampling,
+            filter: FilterSampler::new(sampling.filter),
         }
     }
 
-    fn ray_at(&self, coord: (u32, u32)) -> Ray {
-        let (x, y) = coord;
+    /// Ray through a position on the image plane, in pixels from the top-left corner.
+    /// None where the projection does not cover the image.
+    fn ray_at(&self, x: Distance, y: Distance) -> Option<Ray> {
+        let half_height = self.height as Distance / 2.0;
+        let local = self.projection.ray(
+            (x - self.width as Distance / 2.0) / half_height,
+            (half_height - y) / half_height, // Negative because y-axis is flipped in screen space
+        )?;
+
+        let to_world = |v: Vector3| self.right * v.x + self.up * v.y + self.forward * v.z;
+        Some(Ray::normalized(self.position + to_world(local.origin.coords), to_world(local.direction)))
+    }
+
+    /// Ray from a position on the lens, through the point the pinhole ray has on the plane in focus.
+    /// Rays going sideways or backwards, as with wide projections, focus at the focal distance along the ray.
+    fn lens_ray_at(&self, x: Distance, y: Distance, lens: (Distance, Distance)) -> Option<Ray> {
+        let ray = self.ray_at(x, y)?;
+        if self.aperture.is_pinhole() {
+            return Some(ray);
+        }
 
-        // Compute the world direction for the current pixel
-        let world_direction = self.corner
-            + self.pixel_step_x * x as Distance
-            + self.pixel_step_y * y as Distance;
+        let cosine = ray.direction.dot(&self.forward);
+        let focus = ray.at(self.focal_distance / if cosine > 0.1 { cosine } else { 1.0 });
+        let (lens_x, lens_y) = self.aperture.sample(lens);
+        let origin = ray.origin + self.right * lens_x + self.up * lens_y;
 
-        Ray::normalized(self.base.origin, world_direction)
+        Some(Ray::normalized(origin, focus - origin))
     }
 
-    fn trace_pixel(&self, caster: &impl RayCaster, x: u32, y: u32) -> Rgb<u8> {
-        let ray = self.ray_at((x,y));        
+    fn trace_pixel(&self, caster: &impl RayCaster, x: u32, y: u32) -> Color {
+        let mut sum = [0.0 as ColorPart; 3];
+        let mut total_weight = 0.0 as ColorPart;
+        let mut plain_sum = [0.0 as ColorPart; 3];
+
+        // Samples cover the filter footprint around the pixel center
+        let count = self.sampling.samples_per_pixel.max(1);
+        let samples = self.sampling.pattern.samples(count, (x, y), 0);
+        let lens_samples = if self.aperture.is_pinhole() { vec![] } else { self.sampling.pattern.samples(count, (x, y), 1) };
+        let mut rng = pixel_rng((x, y), 2);
+
+        for (i, position) in samples.iter().enumerate() {
+            let (dx, dy, weight) = self.filter.sample(*position);
+            let weight = weight as ColorPart;
 
-        let Rgb([r,g,b]) = caster.cast(&ray, 5);
-        
-        Rgb([(r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8])
+            let lens = lens_samples.get(i).copied().unwrap_or((0.5, 0.5));
+            let Rgb(color) = match self.lens_ray_at(x as Distance + 0.5 + dx, y as Distance + 0.5 + dy, lens) {
+                Some(ray) => caster.cast(&ray, MAX_DEPTH, &mut rng),
+                None => Rgb([0.0; 3]),
+            };
+
+            for channel in 0..3 {
+                sum[channel] += color[channel] * weight;
+                plain_sum[channel] += color[channel];
+            }
+            total_weight += weight;
+        }
+
+        // Negative lobes can cancel out the weights, fall back to a plain average
+        Rgb(if total_weight > 1e-6 {
+            sum.map(|c| c / total_weight)
+        } else {
+            plain_sum.map(|c| c / samples.len() as ColorPart)
+        })
     }
 
-    pub fn take_photo(&self, caster: &impl RayCaster, on_trace: impl Fn((u32, u32, Rgb<u8>)) + Send + Sync, parallel: bool) -> RgbImage {
-        (if parallel { RgbImage::from_par_fn } else { RgbImage::from_fn })(self.width, self.height, |x, y| {
+    /// Renders the linear colors of the scene, calling `on_trace` as each pixel completes.
+    pub fn take_photo(&self, caster: &impl RayCaster, on_trace: impl Fn((u32, u32, Color)) + Send + Sync, parallel: bool) -> Rgb32FImage {
+        (if parallel { Rgb32FImage::from_par_fn } else { Rgb32FImage::from_fn })(self.width, self.height, |x, y| {
             let rgb = self.trace_pixel(caster, x, y);
             on_trace((x, y, rgb));
             rgb
         })
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn looking_along_up() {
+        let viewpoint = Viewpoint::new(Point3::origin(), Point3::new(0.0, -10.0, 0.0), Vector3::new(0.0, 1.0, 0.0), 50.0);
+        let camera = Camera::new(&viewpoint, 4, 4, SamplingConfig::DEFAULT);
+
+        let ray = camera.ray_at(2.0, 2.0).unwrap();
+        assert!(ray.direction.iter().all(|c| c.is_finite()));
+        assert!(ray.direction.y < -0.9);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::algebra::{Distance, Point3, Ray, Vector3};
+use crate::scene::viewpoint::{ProjectionKind, Viewpoint};
+use std::f64::consts::PI;
+
+/// Maps positions on the image to rays in camera space, where x is right, y is up and z is forward.
+pub trait Projection: Send + Sync {
+    /// Ray through an image position relative to the image center, in units of half the image
+    /// height, so that `y` goes from 1 at the top to -1 at the bottom. None where nothing is seen.
+    fn ray(&self, x: Distance, y: Distance) -> Option<Ray>;
+}
+
+/// Pinhole camera, straight lines stay straight.
+pub struct Perspective {
+    scale: Distance, // Tangent of half the vertical field of view
+}
+
+/// Parallel rays, objects keep their size at any distance.
+pub struct Orthographic {
+    half_height: Distance, // Half the height of the view, in world units
+}
+
+/// Equidistant fisheye, the angle from the view direction grows linearly with the distance
+/// from the image center. The field of view is the diameter of the image circle and can go up to 360°.
+pub struct Fisheye {
+    half_fov: Distance, // In radians
+}
+
+/// Full panorama, longitude along the image width and latitude along the height.
+pub struct Equirectangular {
+    aspect_ratio: Distance,
+}
+
+impl Projection for Perspective {
+    fn ray(&self, x: Distance, y: Distance) -> Option<Ray> {
+        Some(Ray::normalized(Point3::origin(), Vector3::new(x * self.scale, y * self.scale, 1.0)))
+    }
+}
+
+impl Projection for Orthographic {
+    fn ray(&self, x: Distance, y: Distance) -> Option<Ray> {
+        Some(Ray::new(
+            Point3::new(x * self.half_height, y * self.half_height, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        ))
+    }
+}
+
+impl Projection for Fisheye {
+    fn ray(&self, x: Distance, y: Distance) -> Option<Ray> {
+        let radius = (x * x + y * y).sqrt();
+        if radius > 1.0 {
+            return None;
+        }
+
+        let theta = radius * self.half_fov;
+        let phi = y.atan2(x);
+        Some(Ray::new(
+            Point3::origin(),
+            Vector3::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()),
+        ))
+    }
+}
+
+impl Projection for Equirectangular {
+    fn ray(&self, x: Distance, y: Distance) -> Option<Ray> {
+        let longitude = x / self.aspect_ratio * PI;
+        let latitude = y * PI / 2.0;
+        Some(Ray::new(
+            Point3::origin(),
+            Vector3::new(latitude.cos() * longitude.sin(), latitude.sin(), latitude.cos() * longitude.cos()),
+        ))
+    }
+}
+
+/// Creates the projection chosen by the viewpoint, for an image with the given aspect ratio.
+pub fn projection(viewpoint: &Viewpoint, aspect_ratio: Distance) -> Box<dyn Projection> {
+    let half_fov = viewpoint.fov.to_radians() / 2.0;
+    match viewpoint.projection {
+        ProjectionKind::Perspective => Box::new(Perspective { scale: half_fov.tan() }),
+        // Shows the same area as the perspective projection does at the focal distance
+        ProjectionKind::Orthographic => Box::new(Orthographic {
+            half_height: viewpoint.focal_distance() * half_fov.tan(),
+        }),
+        ProjectionKind::Fisheye => Box::new(Fisheye { half_fov }),
+        ProjectionKind::Equirectangular => Box::new(Equirectangular { aspect_ratio }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn projections_look_forward_at_center() {
+        let viewpoint = Viewpoint::DEFAULT;
+        for kind in [ProjectionKind::Perspective, ProjectionKind::Orthographic, ProjectionKind::Fisheye, ProjectionKind::Equirectangular] {
+            let ray = projection(&Viewpoint { projection: kind, ..viewpoint }, 2.0).ray(0.0, 0.0).unwrap();
+            assert!((ray.direction - Vector3::new(0.0, 0.0, 1.0)).magnitude() < 1e-9, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn equirectangular_covers_sphere() {
+        let projection = Equirectangular { aspect_ratio: 2.0 };
+        let behind = projection.ray(2.0, 0.0).unwrap();
+        assert!((behind.direction - Vector3::new(0.0, 0.0, -1.0)).magnitude() < 1e-9);
+        let up = projection.ray(0.0, 1.0).unwrap();
+        assert!((up.direction - Vector3::new(0.0, 1.0, 0.0)).magnitude() < 1e-9);
+    }
+}
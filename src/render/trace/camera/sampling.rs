@@ -0,0 +1,236 @@
+use crate::algebra::{stratum, Distance};
+use clap::ValueEnum;
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+/// How sample positions are distributed within a pixel.
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq)]
+pub enum SamplePattern {
+    /// Regular grid, the same in every pixel
+    Grid,
+    /// One random position in each cell of a grid
+    Stratified,
+    /// Halton sequence in bases 2 and 3, randomly shifted per pixel
+    Halton,
+    /// Sobol (0, 2)-sequence, randomly scrambled per pixel
+    Sobol,
+}
+
+/// Reconstruction filter weighting samples by their distance from the pixel center.
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq)]
+pub enum Filter {
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct SamplingConfig {
+    pub samples_per_pixel: u32,
+    pub pattern: SamplePattern,
+    pub filter: Filter,
+}
+
+impl SamplingConfig {
+    pub const DEFAULT: SamplingConfig = SamplingConfig {
+        samples_per_pixel: 1,
+        pattern: SamplePattern::Stratified,
+        filter: Filter::Box,
+    };
+}
+
+fn unit(bits: u32) -> Distance {
+    bits as Distance / (1u64 << 32) as Distance
+}
+
+fn radical_inverse(mut index: u32, base: u32) -> Distance {
+    let inverse_base = 1.0 / base as Distance;
+    let mut factor = inverse_base;
+    let mut result = 0.0;
+    while index > 0 {
+        result += (index % base) as Distance * factor;
+        index /= base;
+        factor *= inverse_base;
+    }
+    result
+}
+
+/// Second dimension of the Sobol sequence, the first one is the base 2 radical inverse.
+fn sobol2(mut index: u32) -> u32 {
+    let mut v: u32 = 1 << 31;
+    let mut result: u32 = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    result
+}
+
+/// Random numbers for one pixel, the same in every render. Each `dimension` gets its own.
+pub fn pixel_rng((x, y): (u32, u32), dimension: u32) -> SmallRng {
+    SmallRng::seed_from_u64((((x as u64) << 32) | y as u64) ^ ((dimension as u64) << 58))
+}
+
+impl SamplePattern {
+    /// Sample positions in the unit square for one pixel. Each `dimension` (pixel area, lens...)
+    /// gets its own positions, shuffled so that they are not correlated with the other dimensions.
+    pub fn samples(&self, count: u32, pixel: (u32, u32), dimension: u32) -> Vec<(Distance, Distance)> {
+        let mut rng = pixel_rng(pixel, dimension);
+
+        // Random per pixel, shifting keeps Halton points well spread, xor keeps the Sobol strata
+        let shift: (Distance, Distance) = (rng.gen(), rng.gen());
+        let scramble: (u32, u32) = (rng.gen(), rng.gen());
+        let wrap = |v: Distance| v - v.floor();
+
+        let mut samples: Vec<(Distance, Distance)> = (0..count)
+            .map(|i| match self {
+                SamplePattern::Grid => stratum(i, count, (0.5, 0.5)),
+                SamplePattern::Stratified => stratum(i, count, rng.gen()),
+                SamplePattern::Halton => (wrap(radical_inverse(i + 1, 2) + shift.0), wrap(radical_inverse(i + 1, 3) + shift.1)),
+                SamplePattern::Sobol => (unit(i.reverse_bits() ^ scramble.0), unit(sobol2(i) ^ scramble.1)),
+            })
+            .collect();
+
+        if dimension > 0 {
+            samples.shuffle(&mut rng);
+        }
+        samples
+    }
+}
+
+impl Filter {
+    /// Distance from the pixel center, in pixels, where the weight drops to zero.
+    pub fn radius(&self) -> Distance {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.0,
+            Filter::Gaussian => 1.5,
+            Filter::Mitchell => 2.0,
+        }
+    }
+
+    fn weight_1d(&self, d: Distance) -> Distance {
+        let d = d.abs();
+        let radius = self.radius();
+        if d > radius {
+            return 0.0;
+        }
+
+        match self {
+            Filter::Box => 1.0,
+            Filter::Tent => radius - d,
+            Filter::Gaussian => {
+                const ALPHA: Distance = 2.0;
+                ((-ALPHA * d * d).exp() - (-ALPHA * radius * radius).exp()).max(0.0)
+            }
+            Filter::Mitchell => {
+                // Mitchell-Netravali with B = C = 1/3
+                const B: Distance = 1.0 / 3.0;
+                const C: Distance = 1.0 / 3.0;
+                let polynomial = if d < 1.0 {
+                    (12.0 - 9.0 * B - 6.0 * C) * d * d * d + (-18.0 + 12.0 * B + 6.0 * C) * d * d + (6.0 - 2.0 * B)
+                } else {
+                    (-B - 6.0 * C) * d * d * d + (6.0 * B + 30.0 * C) * d * d + (-12.0 * B - 48.0 * C) * d + (8.0 * B + 24.0 * C)
+                };
+                polynomial / 6.0
+            }
+        }
+    }
+}
+
+/// Places samples in proportion to the absolute filter weight, so that every sample counts
+/// fully, with the sign of the filter. This is much less noisy than weighting uniform samples.
+pub struct FilterSampler {
+    filter: Filter,
+    cdf: Vec<Distance>, // Cumulative absolute weight over [0, radius] in one dimension
+}
+
+impl FilterSampler {
+    const RESOLUTION: usize = 256;
+
+    pub fn new(filter: Filter) -> Self {
+        let step = filter.radius() / Self::RESOLUTION as Distance;
+        let mut cdf = Vec::with_capacity(Self::RESOLUTION);
+        let mut total = 0.0;
+        for i in 0..Self::RESOLUTION {
+            total += filter.weight_1d((i as Distance + 0.5) * step).abs();
+            cdf.push(total);
+        }
+        cdf.iter_mut().for_each(|c| *c /= total);
+
+        Self { filter, cdf }
+    }
+
+    fn sample_1d(&self, u: Distance) -> (Distance, Distance) {
+        // The filters are symmetric, use one half of the unit interval for each side
+        let (side, u) = if u < 0.5 { (-1.0, u * 2.0) } else { (1.0, u * 2.0 - 1.0) };
+
+        let bin = self.cdf.partition_point(|c| *c < u).min(Self::RESOLUTION - 1);
+        let start = if bin == 0 { 0.0 } else { self.cdf[bin - 1] };
+        let width = self.cdf[bin] - start;
+        let fraction = if width > 0.0 { (u - start) / width } else { 0.5 };
+
+        let offset = (bin as Distance + fraction) / Self::RESOLUTION as Distance * self.filter.radius();
+        (side * offset, self.filter.weight_1d(offset).signum())
+    }
+
+    /// Offset from the pixel center and sample weight for a position in the unit square.
+    pub fn sample(&self, position: (Distance, Distance)) -> (Distance, Distance, Distance) {
+        let (dx, wx) = self.sample_1d(position.0);
+        let (dy, wy) = self.sample_1d(position.1);
+        (dx, dy, wx * wy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_cover_strata() {
+        for pattern in [SamplePattern::Grid, SamplePattern::Stratified, SamplePattern::Halton, SamplePattern::Sobol] {
+            let samples = pattern.samples(16, (3, 7), 0);
+            assert_eq!(samples.len(), 16);
+            assert!(samples.iter().all(|(u, v)| (0.0..1.0).contains(u) && (0.0..1.0).contains(v)));
+
+            if pattern != SamplePattern::Halton {
+                // Grid and (0, 2)-sequences put exactly one sample in each 4x4 cell
+                let mut cells: Vec<_> = samples.iter().map(|(u, v)| ((u * 4.0) as u32, (v * 4.0) as u32)).collect();
+                cells.sort();
+                cells.dedup();
+                assert_eq!(cells.len(), 16, "{:?}", pattern);
+            }
+        }
+    }
+
+    /// Counts that are not squares still get one sample in each of as many cells of equal area.
+    #[test]
+    fn stratified_covers_square_for_any_count() {
+        for count in [2, 3, 5, 7, 8, 10] {
+            let samples = SamplePattern::Stratified.samples(count, (1, 2), 0);
+            for i in 0..count {
+                let (low, high) = (stratum(i, count, (0.0, 0.0)), stratum(i, count, (1.0, 1.0)));
+                assert!(((high.0 - low.0) * (high.1 - low.1) - 1.0 / count as Distance).abs() < 1e-9);
+                let (u, v) = samples[i as usize];
+                assert!((low.0..high.0).contains(&u) && (low.1..high.1).contains(&v), "{} {}", count, i);
+            }
+
+            // Strata reach the top of the square
+            let top = (0..count).map(|i| stratum(i, count, (1.0, 1.0)).1).fold(0.0, Distance::max);
+            assert!((top - 1.0).abs() < 1e-9, "{} {}", count, top);
+        }
+    }
+
+    #[test]
+    fn box_filter_samples_pixel() {
+        let sampler = FilterSampler::new(Filter::Box);
+        let (dx, dy, weight) = sampler.sample((0.25, 0.9));
+        assert!((dx + 0.25).abs() < 1e-9 && (dy - 0.4).abs() < 1e-9);
+        assert_eq!(weight, 1.0);
+    }
+}
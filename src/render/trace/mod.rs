@@ -1,10 +1,8 @@
-use crate::algebra::{Point3, Vector3};
-use crate::render::trace::camera::Camera;
-use crate::render::trace::world::World;
+use crate::render::trace::camera::{Camera, SamplingConfig};
+use crate::render::trace::world::{AcceleratorConfig, IntegratorConfig, PathTracer, World};
 use crate::render::{RenderListener, Renderer};
-use crate::algebra::Ray;
 use crate::scene::Scene;
-use image::RgbImage;
+use image::Rgb32FImage;
 use std::time::Instant;
 
 pub mod world;
@@ -14,8 +12,12 @@ pub struct TraceRenderer {
     config: TraceRenderConfig
 }
 
+#[derive(Debug, Copy, Clone)]
 pub struct TraceRenderConfig {
     pub parallel: bool,
+    pub accelerator: AcceleratorConfig,
+    pub sampling: SamplingConfig,
+    pub integrator: IntegratorConfig,
 }
 impl TraceRenderer {
     pub fn new(config: TraceRenderConfig) -> TraceRenderer {
@@ -25,13 +27,15 @@ impl TraceRenderer {
     }
 }
 impl Renderer for TraceRenderer {
-    fn render(&self, scene: &Scene, width: u32, height: u32, tx: impl RenderListener) -> RgbImage {
-        let world = World::from_scene(scene);
-        let camera_base = Ray::normalized(Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.1, 1.0).normalize());
-        let camera = Camera::new(camera_base, width, height, 50.0);
+    fn render(&self, scene: &Scene, width: u32, height: u32, tx: impl RenderListener) -> Rgb32FImage {
+        let world = World::from_scene(scene, &self.config.accelerator);
+        let camera = Camera::new(scene.viewpoint(), width, height, self.config.sampling);
 
         let photo_start_time = Instant::now();
-        let image = camera.take_photo(&world, tx, self.config.parallel);
+        let image = match self.config.integrator {
+            IntegratorConfig::Whitted => camera.take_photo(&world, tx, self.config.parallel),
+            IntegratorConfig::Path(config) => camera.take_photo(&PathTracer::new(&world, config), tx, self.config.parallel),
+        };
         let photo_duration = photo_start_time.elapsed();
         println!("Photo generation completed in: {:?}", photo_duration);
         image
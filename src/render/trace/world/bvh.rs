@@ -0,0 +1,79 @@
+use crate::algebra::{Bounded, BoundingBox, Bvh, BvhConfig, Distance, Ray};
+use crate::render::trace::world::intersect::{Intersecting, Intersection};
+use std::fmt;
+use std::sync::Arc;
+
+/// Surface area heuristic bounding volume hierarchy over whole objects.
+pub struct BvhTree {
+    bvh: Bvh,
+    objects: Vec<Arc<dyn Intersecting>>,
+    outside: Vec<Arc<dyn Intersecting>>, // Objects not fit for tree, such as infinite objects
+}
+
+impl fmt::Debug for BvhTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BvhTree {{ bvh: {:?}, outside: {} }}", self.bvh, self.outside.len())
+    }
+}
+
+impl BvhTree {
+    pub fn new(config: BvhConfig, objects: Vec<Arc<dyn Intersecting>>) -> Self {
+        // Objects with infinite size cannot be handled with the tree
+        let (outside, objects): (Vec<_>, Vec<_>) = objects.into_iter().partition(|o| o.bounding_box().is_infinite());
+
+        let bounds: Vec<BoundingBox> = objects.iter().map(|o| o.bounding_box()).collect();
+        let bvh = Bvh::build(&bounds, &config);
+
+        Self { bvh, objects, outside }
+    }
+}
+
+impl Intersecting for BvhTree {
+    fn closest_intersection(&self, ray: &Ray, max: Distance) -> Option<Intersection<'_>> {
+        let result = self.bvh.closest(ray, max, |index, max| {
+            self.objects[index].closest_intersection(ray, max).map(|i| (i.distance, i))
+        });
+
+        if let Some(result2) = self.outside.closest_intersection(ray, result.as_ref().map_or(max, |i| i.distance)) {
+            return Some(result2);
+        }
+
+        result
+    }
+
+    fn any_intersects(&self, ray: &Ray, max: Distance) -> bool {
+        if self.bvh.any(ray, max, |index, max| self.objects[index].any_intersects(ray, max)) {
+            return true;
+        }
+
+        self.outside.any_intersects(ray, max)
+    }
+}
+
+impl Bounded for BvhTree {
+    fn bounding_box(&self) -> BoundingBox {
+        // Infinite objects make the whole tree infinite
+        self.outside.iter().fold(self.bvh.bounding_box(), |bounds, o| bounds.union(&o.bounding_box()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::{Point3, Vector3};
+    use crate::scene::object::Object;
+    use crate::scene::surface::Surface;
+    use crate::scene::material::Material;
+    use image::Rgb;
+
+    #[test]
+    fn bounds_include_infinite_objects() {
+        let gray = Surface::new(Rgb([0.5, 0.5, 0.5]), Material::DEFAULT);
+        let sphere: Arc<dyn Intersecting> = Arc::new(Object::sphere(Point3::origin(), 1.0, &gray));
+        let plane: Arc<dyn Intersecting> = Arc::new(Object::plane(Point3::origin(), Vector3::new(0.0, 1.0, 0.0), &gray));
+
+        let bounded = BvhTree::new(BvhConfig::DEFAULT, vec![sphere.clone()]);
+        assert!(!bounded.bounding_box().is_infinite());
+        assert!(BvhTree::new(BvhConfig::DEFAULT, vec![sphere, plane]).bounding_box().is_infinite());
+    }
+}
@@ -1,66 +1,116 @@
-use crate::algebra::{Distance, DistanceConstants, Point3, UnitVector3};
+use crate::algebra::{cosine_direction, stratum, Distance, DistanceConstants, Point3, UnitVector3, Vector3, VectorOps};
 use crate::algebra::Ray;
+use crate::render::trace::world::bvh::BvhTree;
 use crate::render::trace::world::intersect::Intersection;
-use crate::render::trace::world::otree::{Octree, OctreeConfig};
+use crate::render::trace::world::otree::Octree;
+pub use crate::algebra::BvhConfig;
+pub use crate::render::trace::world::otree::OctreeConfig;
+pub use crate::scene::environment::Environment;
 pub use crate::scene::geometry::Geometry;
-pub use crate::scene::light::Light;
-pub use crate::scene::ray::RayCaster;
+pub use crate::scene::light::{Ambient, AreaLight, Light, LightSample};
+pub use crate::scene::ray::{DirectLight, RayCaster};
 use crate::scene::{Color, ColorPart, Scene};
+use crate::scene::object::Object;
 use image::{Pixel, Rgb};
 use intersect::Intersecting;
+use rand::rngs::SmallRng;
+use rand::Rng;
 use std::sync::Arc;
 
 mod intersect;
 mod otree;
+mod bvh;
+mod path;
+
+pub use path::{PathConfig, PathTracer};
+
+/// Acceleration structure used to find the objects hit by rays.
+#[derive(Debug, Copy, Clone)]
+pub enum AcceleratorConfig {
+    /// Tests every object, best for a handful of objects
+    Flat,
+    Octree(OctreeConfig),
+    Bvh(BvhConfig),
+}
+
+/// How the light reaching the camera is computed.
+#[derive(Debug, Copy, Clone)]
+pub enum IntegratorConfig {
+    /// Recursive ray tracing of mirror and refraction rays, with direct light only
+    Whitted,
+    /// Monte Carlo path tracing with indirect light
+    Path(PathConfig),
+}
 
 pub struct World {
     root: Box<dyn Intersecting>,
     //root: Vec<Arc<dyn Intersecting>>,
     lights: Vec<Light>,
-}
-
-fn min(v1: ColorPart, v2: ColorPart) -> ColorPart {
-    if v1 < v2 {
-        v1
-    } else {
-        v2
-    }
+    environment: Environment,
+    ambient: Ambient,
 }
 
 impl World {
-    pub fn new(objects: Vec<Arc<dyn Intersecting>>) -> World {
+    pub fn new(objects: Vec<Arc<dyn Intersecting>>, accelerator: &AcceleratorConfig) -> World {
         World {
-            root: if objects.len() > 20 {
-                Box::new(Octree::new(OctreeConfig::new(8, 5, 1.5), objects))
-            } else {
-                Box::new(objects)
+            root: match accelerator {
+                AcceleratorConfig::Flat => Box::new(objects),
+                AcceleratorConfig::Octree(config) => Box::new(Octree::new(*config, objects)),
+                AcceleratorConfig::Bvh(config) => Box::new(BvhTree::new(*config, objects)),
             },
             lights: Vec::new(),
+            environment: Environment::BLACK,
+            ambient: Ambient::NONE,
         }
     }
 
-    pub fn from_scene(scene: &Scene) -> World {
+    pub fn from_scene(scene: &Scene, accelerator: &AcceleratorConfig) -> World {
+        // Area lights are also seen as glowing shapes
+        let light_objects = scene.iter_lights().filter_map(|light| match light {
+            Light::Area(area) => Some(area.object.clone()),
+            _ => None,
+        });
+
         let mut w = World::new(
             scene
                 .iter_objects()
-                .map(|o| o.clone() as Arc<dyn Intersecting>)
+                .cloned()
+                .chain(light_objects)
+                .map(|o| o as Arc<dyn Intersecting>)
                 .collect(),
+            accelerator,
         );
 
+        w.set_environment(scene.environment().clone());
+        w.set_ambient(*scene.ambient());
         scene.iter_lights().for_each(|light| {
-            w.add_light(*light);
+            w.add_light(light.clone());
         });
 
+        // Glowing objects light the scene too, unless their shape cannot be sampled
+        scene
+            .iter_objects()
+            .filter(|o| o.texture.emits_light() && o.geometry.can_sample())
+            .for_each(|o| w.add_light(Light::Area(AreaLight::new(o.clone(), AreaLight::DEFAULT_SAMPLES))));
+
         w
     }
 
+    pub fn set_environment(&mut self, environment: Environment) {
+        self.environment = environment;
+    }
+
+    pub fn set_ambient(&mut self, ambient: Ambient) {
+        self.ambient = ambient;
+    }
+
     pub fn add_light(&mut self, light: Light) {
         self.lights.push(light);
     }
 }
 
 impl RayCaster for World {
-    fn cast(&self, ray: &Ray, depth: u32) -> Color {
+    fn cast(&self, ray: &Ray, depth: u32, rng: &mut SmallRng) -> Color {
         if depth == 0 {
             return Rgb([0.0, 0.0, 0.0]);
         }
@@ -71,33 +121,127 @@ impl RayCaster for World {
                 let hr = i.object.hit(ray, i.distance);
                 let surface = i.object.surface_at(&hr);
 
-                surface.material.shade(ray, &hr, surface.color, self, depth)
+                surface.material.shade(ray, &hr, surface.color, self, depth, rng)
             })
-            .unwrap_or(Rgb([0.0, 0.0, 0.0]))
+            .unwrap_or_else(|| self.environment.radiance(&ray.direction))
     }
 
-    fn direct_lightning(&self, position: &Point3, normal: &UnitVector3) -> Color {
-        let mut c = Color::from([0.0 as ColorPart, 0.0, 0.0]);
+    /// The environment is sampled with shadow rays as the lights are.
+    fn direct_lightning(&self, position: &Point3, normal: &UnitVector3, view: &Vector3, shininess: Distance, rng: &mut SmallRng) -> DirectLight {
+        let sky = self.environment_light(position, normal, &|_| Rgb([1.0, 1.0, 1.0]), rng);
+        let direct = self.light_sources(position, normal, Some((view, shininess)), rng);
+        DirectLight {
+            diffuse: direct.diffuse.map2(&sky, |x1, x2| x1 + x2),
+            ..direct
+        }
+    }
 
-        for l in self.lights.iter() {
-            let color = l.illuminate(*position, *normal);
-            if color != World::BLACK {
-                let ray = Ray::new(*position, normal.into_inner());
-                if !self.is_shadowed(ray.at(Distance::OFF_SURFACE), l) {
-                    c = c.map2(&color, |x1, x2| min(1.0, x1 + x2))
-                }
-            }
+    /// The environment is sampled with shadow rays as the lights are.
+    fn reflected_lightning(&self, position: &Point3, normal: &UnitVector3, reflectance: &dyn Fn(&Vector3) -> Color, rng: &mut SmallRng) -> Color {
+        let sky = self.environment_light(position, normal, reflectance, rng);
+        self.reflected_light_sources(position, normal, reflectance, rng).map2(&sky, |x1, x2| x1 + x2)
+    }
+
+    fn ambient_light(&self, position: &Point3, normal: &UnitVector3, rng: &mut SmallRng) -> Color {
+        let Some(occlusion) = self.ambient.occlusion else {
+            return self.ambient.color;
+        };
+        if self.ambient.color == World::BLACK {
+            return World::BLACK;
         }
 
-        c
+        // Rays near the normal count more, as the light they would bring
+        let off_surface = Ray::new(*position, normal.into_inner()).at(Distance::OFF_SURFACE);
+        let open = (0..occlusion.samples)
+            .filter(|_| {
+                let ray = Ray::normalized(off_surface, cosine_direction(normal, rng.gen()));
+                !self.is_something_within_distance(&ray, occlusion.distance)
+            })
+            .count();
+
+        let fraction = open as ColorPart / occlusion.samples as ColorPart;
+        self.ambient.color.map(|c| c * fraction)
     }
 }
 
 impl World {
     const BLACK: Color = Rgb([0.0, 0.0, 0.0]);
+    const ENVIRONMENT_SAMPLES: u32 = 16;
+
+    /// Light of the environment reaching a point past the objects around it, in directions
+    /// chosen by the environment, reflected by a BRDF.
+    fn environment_light(&self, position: &Point3, normal: &UnitVector3, reflectance: &dyn Fn(&Vector3) -> Color, rng: &mut SmallRng) -> Color {
+        if self.environment.is_black() {
+            return World::BLACK;
+        }
+
+        let off_surface = Ray::new(*position, normal.into_inner()).at(Distance::OFF_SURFACE);
+        let count = World::ENVIRONMENT_SAMPLES;
+        let mut c = World::BLACK;
+        for i in 0..count {
+            let Some(sample) = self.environment.sample(normal, stratum(i, count, rng.gen())) else {
+                continue;
+            };
+            if !self.is_something_within_distance(&Ray::new(off_surface, sample.direction), Distance::INFINITY) {
+                let reflected = reflectance(&sample.direction).map2(&sample.color, |r, x| r * x / count as ColorPart);
+                c = c.map2(&reflected, |x1, x2| x1 + x2);
+            }
+        }
+        c
+    }
+
+    /// Calls `visit` with every light sample reaching a point, and the weight of the sample
+    /// among those of its light.
+    fn visit_light_samples(&self, position: &Point3, normal: &UnitVector3, rng: &mut SmallRng, mut visit: impl FnMut(&LightSample, ColorPart)) {
+        let off_surface = Ray::new(*position, normal.into_inner()).at(Distance::OFF_SURFACE);
 
-    fn is_shadowed(&self, position: Point3, light: &Light) -> bool {
-        self.is_something_within_distance(&light.towards(position), 1.0)
+        for l in self.lights.iter() {
+            // Stratified over the light, so that penumbrae get smooth quickly
+            let count = l.sample_count();
+            for i in 0..count {
+                let u = stratum(i, count, rng.gen());
+                if let Some(sample) = l.sample(off_surface, *normal, u) {
+                    if !self.is_something_within_distance(&sample.shadow_ray, sample.distance) {
+                        visit(&sample, 1.0 / count as ColorPart);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Light arriving directly from the lights of the scene, but not from the environment.
+    /// Highlights are only computed for a view direction and shininess.
+    fn light_sources(&self, position: &Point3, normal: &UnitVector3, highlight: Option<(&Vector3, Distance)>, rng: &mut SmallRng) -> DirectLight {
+        let mut diffuse = World::BLACK;
+        let mut specular = World::BLACK;
+
+        self.visit_light_samples(position, normal, rng, |sample, weight| {
+            diffuse = diffuse.map2(&sample.color, |x1, x2| x1 + x2 * weight);
+            if let Some((view, shininess)) = highlight {
+                // Blinn-Phong, with the half vector between the light and the viewer
+                let half = sample.shadow_ray.direction.normalize() - view.normalize();
+                let strength = half.cos_angle(normal).max(0.0).powf(shininess) as ColorPart;
+                specular = specular.map2(&sample.color, |x1, x2| x1 + x2 * strength * weight);
+            }
+        });
+
+        DirectLight { diffuse, specular }
+    }
+
+    /// Light arriving directly from the lights of the scene and reflected by a BRDF.
+    fn reflected_light_sources(&self, position: &Point3, normal: &UnitVector3, reflectance: &dyn Fn(&Vector3) -> Color, rng: &mut SmallRng) -> Color {
+        let mut c = World::BLACK;
+        self.visit_light_samples(position, normal, rng, |sample, weight| {
+            let reflected = reflectance(&sample.shadow_ray.direction).map2(&sample.color, |r, x| r * x * weight);
+            c = c.map2(&reflected, |x1, x2| x1 + x2);
+        });
+        c
+    }
+
+    /// Whether the object is the shape of an area light, whose light is already counted
+    /// by `direct_lightning`.
+    fn is_light(&self, object: &Object) -> bool {
+        self.lights.iter().any(|light| matches!(light, Light::Area(area) if std::ptr::eq(area.object.as_ref(), object)))
     }
 
     fn is_something_within_distance(&self, ray: &Ray, distance: Distance) -> bool {
@@ -107,3 +251,86 @@ impl World {
         self.root.closest_intersection(ray, Distance::MAX)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::material::Material;
+    use crate::scene::surface::Surface;
+    use rand::SeedableRng;
+
+    #[test]
+    fn emissive_object_lights_floor() {
+        let mut scene = Scene::new();
+        let floor = Surface::new(Rgb([1.0, 1.0, 1.0]), Material::DEFAULT);
+        let glowing = Surface::new(Rgb([0.0, 0.0, 0.0]), Material { emission: Rgb([5.0, 5.0, 5.0]), ..Material::DEFAULT });
+        scene.add(Object::plane(Point3::origin(), Vector3::new(0.0, 1.0, 0.0), &floor));
+        scene.add(Object::sphere(Point3::new(0.0, 3.0, 0.0), 1.0, &glowing));
+        // Planes cannot be sampled, and do not become lights
+        scene.add(Object::plane(Point3::new(0.0, 100.0, 0.0), Vector3::new(0.0, -1.0, 0.0), &glowing));
+
+        let world = World::from_scene(&scene, &AcceleratorConfig::Flat);
+        assert_eq!(world.lights.len(), 1);
+
+        let up = UnitVector3::new_normalize(Vector3::new(0.0, 1.0, 0.0));
+        let mut rng = SmallRng::seed_from_u64(16);
+        let runs = 500;
+        let r = (0..runs).map(|_| world.light_sources(&Point3::origin(), &up, None, &mut rng).diffuse[0]).sum::<ColorPart>() / runs as ColorPart;
+        // A sphere of radiance L seen from a distance d to its center gives L * r^2 / d^2
+        assert!((r - 5.0 / 9.0).abs() < 0.05, "{}", r);
+    }
+
+    #[test]
+    fn occlusion_darkens_ambient() {
+        let mut scene = Scene::new();
+        let gray = Surface::new(Rgb([0.5, 0.5, 0.5]), Material::DEFAULT);
+        scene.add(Object::plane(Point3::origin(), Vector3::new(0.0, 1.0, 0.0), &gray));
+        scene.add(Object::plane(Point3::new(0.0, 1.0, 0.0), Vector3::new(0.0, -1.0, 0.0), &gray));
+        let ambient = Ambient::new(Rgb([0.2, 0.2, 0.2]));
+        let up = UnitVector3::new_normalize(Vector3::new(0.0, 1.0, 0.0));
+
+        let mut rng = SmallRng::seed_from_u64(19);
+        let mut world = World::from_scene(&scene, &AcceleratorConfig::Flat);
+        world.set_ambient(ambient);
+        assert_eq!(world.ambient_light(&Point3::origin(), &up, &mut rng), Rgb([0.2, 0.2, 0.2]));
+
+        // Covered by the ceiling, unless it is farther than the occlusion distance
+        world.set_ambient(ambient.with_occlusion(16, 1000.0));
+        assert_eq!(world.ambient_light(&Point3::origin(), &up, &mut rng), Rgb([0.0, 0.0, 0.0]));
+        world.set_ambient(ambient.with_occlusion(16, 0.5));
+        assert_eq!(world.ambient_light(&Point3::origin(), &up, &mut rng), Rgb([0.2, 0.2, 0.2]));
+    }
+
+    #[test]
+    fn highlight_where_light_is_mirrored() {
+        let mut scene = Scene::new();
+        scene.add_light(Light::new(Ray::new(Point3::new(0.0, 10.0, 0.0), Vector3::new(0.0, -1.0, 0.0)), Rgb([1.0, 1.0, 1.0])));
+        let world = World::from_scene(&scene, &AcceleratorConfig::Flat);
+        let up = UnitVector3::new_normalize(Vector3::new(0.0, 1.0, 0.0));
+        let mut rng = SmallRng::seed_from_u64(22);
+
+        let mirrored = world.direct_lightning(&Point3::origin(), &up, &Vector3::new(0.0, -1.0, 0.0), 32.0, &mut rng);
+        assert_eq!(mirrored.diffuse, Rgb([1.0, 1.0, 1.0]));
+        assert!((mirrored.specular[0] - 1.0).abs() < 1e-6);
+
+        let aside = world.direct_lightning(&Point3::origin(), &up, &Vector3::new(1.0, -1.0, 0.0), 32.0, &mut rng);
+        assert!(aside.specular[0] < 0.1, "{:?}", aside.specular);
+    }
+
+    #[test]
+    fn environment_is_shadowed() {
+        let mut scene = Scene::new();
+        let gray = Surface::new(Rgb([0.5, 0.5, 0.5]), Material::DEFAULT);
+        scene.set_environment(Environment::Color(Rgb([1.0, 1.0, 1.0])));
+        let open = World::from_scene(&scene, &AcceleratorConfig::Flat);
+        scene.add(Object::sphere(Point3::origin(), 5.0, &gray));
+        let closed = World::from_scene(&scene, &AcceleratorConfig::Flat);
+
+        let up = UnitVector3::new_normalize(Vector3::new(0.0, 1.0, 0.0));
+        let view = Vector3::new(0.0, -1.0, 0.0);
+        let mut rng = SmallRng::seed_from_u64(17);
+        let lit = open.direct_lightning(&Point3::origin(), &up, &view, 1.0, &mut rng).diffuse;
+        assert!((lit[0] - 1.0).abs() < 1e-6, "{:?}", lit);
+        assert_eq!(closed.direct_lightning(&Point3::origin(), &up, &view, 1.0, &mut rng).diffuse, Rgb([0.0, 0.0, 0.0]));
+    }
+}
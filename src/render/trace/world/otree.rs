@@ -3,7 +3,7 @@ use crate::render::trace::world::intersect::{Intersecting, Intersection};
 use std::fmt;
 use std::sync::Arc;
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 pub struct OctreeConfig {
     pub max_objects: usize,
     pub max_depth: usize,
@@ -11,6 +11,12 @@ pub struct OctreeConfig {
 }
 
 impl OctreeConfig {
+    pub const DEFAULT: OctreeConfig = OctreeConfig {
+        max_objects: 8,
+        max_depth: 5,
+        loose_factor: 1.5,
+    };
+
     pub fn new(max_objects: usize, max_depth: usize, loose_factor: Distance) -> Self {
         Self {
             max_objects,
@@ -0,0 +1,296 @@
+use crate::algebra::{cosine_direction, Distance, DistanceConstants, Point3, Ray, UnitVector3, Vector3};
+use crate::render::trace::world::World;
+use crate::scene::geometry::Geometry;
+use crate::scene::material::Material;
+use crate::scene::ray::{DirectLight, RayCaster};
+use crate::scene::{Color, ColorPart};
+use image::{Pixel, Rgb};
+use rand::rngs::SmallRng;
+use rand::Rng;
+
+#[derive(Debug, Copy, Clone)]
+pub struct PathConfig {
+    pub max_bounces: u32,
+    pub roulette_bounces: u32, // Bounces before Russian roulette may end a path
+}
+
+impl PathConfig {
+    pub const DEFAULT: PathConfig = PathConfig {
+        max_bounces: 8,
+        roulette_bounces: 3,
+    };
+
+    pub fn new(max_bounces: u32, roulette_bounces: u32) -> Self {
+        Self { max_bounces, roulette_bounces }
+    }
+}
+
+/// Monte Carlo path tracer following one random path per camera ray, which gives indirect
+/// light and color bleeding. Light arriving directly from the lights is added at every
+/// diffuse hit in the same way as the Whitted tracer does.
+pub struct PathTracer<'a> {
+    world: &'a World,
+    config: PathConfig,
+}
+
+impl<'a> PathTracer<'a> {
+    pub fn new(world: &'a World, config: PathConfig) -> Self {
+        Self { world, config }
+    }
+
+    /// Light from the lights and, if `sky` is set, from one direction of the environment chosen
+    /// where it is bright, reflected by a BRDF.
+    fn direct_light(&self, position: &Point3, normal: &UnitVector3, reflectance: &dyn Fn(&Vector3) -> Color, sky: bool, rng: &mut SmallRng) -> Color {
+        let lights = self.world.reflected_light_sources(position, normal, reflectance, rng);
+        let Some(sample) = sky.then(|| self.world.environment.sample(normal, rng.gen())).flatten() else {
+            return lights;
+        };
+
+        let shadow_ray = Ray::new(*position + normal.into_inner() * Distance::OFF_SURFACE, sample.direction);
+        if self.world.is_something_within_distance(&shadow_ray, Distance::INFINITY) {
+            return lights;
+        }
+        let reflected = reflectance(&sample.direction).map2(&sample.color, |x1, x2| x1 * x2);
+        lights.map2(&reflected, |x1, x2| x1 + x2)
+    }
+}
+
+impl RayCaster for PathTracer<'_> {
+    /// Paths are limited by the configuration, `depth` is not used.
+    fn cast(&self, ray: &Ray, _depth: u32, rng: &mut SmallRng) -> Color {
+        let mut color = [0.0 as ColorPart; 3];
+        let mut throughput = [1.0 as ColorPart; 3];
+        let mut ray = *ray;
+        let mut specular = true; // Camera, mirror and refraction rays see lights, the others had them sampled
+        let mut sky_sampled = false; // Only diffuse bounces sample the environment, glossy ones find it better by themselves
+        let mut medium: Option<Material> = None; // Material the path is inside of, entered by refraction
+
+        for bounce in 0..self.config.max_bounces {
+            let Some(intersection) = self.world.cast_intersection(&ray) else {
+                if !sky_sampled {
+                    let Rgb(sky) = self.world.environment.radiance(&ray.direction);
+                    for i in 0..3 {
+                        color[i] += throughput[i] * sky[i];
+                    }
+                }
+                break;
+            };
+            let hr = intersection.object.hit(&ray, intersection.distance);
+            let surface = intersection.object.surface_at(&hr);
+            let material = surface.material;
+            let Rgb(albedo) = surface.color;
+
+            if let Some(inside) = medium {
+                let Rgb(transmittance) = inside.absorbed((hr.position - ray.origin).magnitude());
+                for i in 0..3 {
+                    throughput[i] *= transmittance[i];
+                }
+            }
+            let incoming = ray.direction;
+
+            // Lights are sampled on the side they shine from, their back is only found by paths
+            let sampled = !specular && self.world.is_light(intersection.object) && hr.normal.dot(&ray.direction) < 0.0;
+            if !sampled {
+                for i in 0..3 {
+                    color[i] += throughput[i] * material.emission[i];
+                }
+            }
+
+            specular = true;
+            sky_sampled = false;
+
+            // A coat reflects in proportion to its Fresnel reflectance, the rest of the light is
+            // followed through it to the material below
+            let mut coat_reflection = None;
+            if let Some(coat) = material.coat {
+                let view = -ray.direction;
+                let normal = if hr.normal.dot(&view) < 0.0 { -hr.normal } else { hr.normal };
+                let reflectance = |light: &Vector3| coat.reflectance(&normal, &view, light);
+                let Rgb(direct) = self.direct_light(&hr.position, &normal, &reflectance, false, rng);
+                for i in 0..3 {
+                    color[i] += throughput[i] * direct[i];
+                }
+
+                let fresnel = coat.fresnel(&normal, &view);
+                if rng.gen::<ColorPart>() < fresnel {
+                    let Some(sample) = coat.sample(&normal, &view, rng.gen()) else {
+                        break;
+                    };
+                    coat_reflection = Some((sample, normal, fresnel));
+                } else {
+                    let Rgb(transmittance) = coat.transmittance(&normal, &view);
+                    for i in 0..3 {
+                        throughput[i] *= transmittance[i];
+                    }
+                }
+            }
+
+            if let Some(((direction, Rgb(weight)), normal, fresnel)) = coat_reflection {
+                specular = false;
+                ray = Ray::normalized(hr.position + normal.into_inner() * Distance::OFF_SURFACE, direction);
+                for i in 0..3 {
+                    throughput[i] *= weight[i] / fresnel;
+                }
+            } else if let Some(pbr) = material.pbr {
+                let view = -ray.direction;
+                let normal = if hr.normal.dot(&view) < 0.0 { -hr.normal } else { hr.normal };
+                let reflectance = |light: &Vector3| pbr.reflectance(surface.color, &normal, &view, light);
+                let Rgb(direct) = self.direct_light(&hr.position, &normal, &reflectance, false, rng);
+                for i in 0..3 {
+                    color[i] += throughput[i] * direct[i];
+                }
+
+                // Lights were sampled, whichever lobe the reflected ray follows
+                let Some((direction, Rgb(weight))) = pbr.sample(surface.color, &normal, &view, rng.gen(), rng.gen()) else {
+                    break;
+                };
+                specular = false;
+                ray = Ray::normalized(hr.position + normal.into_inner() * Distance::OFF_SURFACE, direction);
+                for i in 0..3 {
+                    throughput[i] *= weight[i];
+                }
+            } else if material.is_dielectric() {
+                // Reflected or refracted in proportion to the Fresnel reflectance
+                ray = match material.glossy_refracted_ray(&ray, &hr, rng.gen()) {
+                    Some(refracted) if rng.gen::<ColorPart>() >= material.reflectance(&ray, &hr) => refracted,
+                    _ => material.glossy_reflected_ray(&ray, &hr, rng.gen()).unwrap_or_else(|| Material::reflected_ray(&ray, &hr)),
+                };
+            } else if rng.gen::<ColorPart>() < material.reflectivity {
+                // The mirror is followed in proportion to the reflectivity
+                ray = material.glossy_reflected_ray(&ray, &hr, rng.gen()).unwrap_or_else(|| Material::reflected_ray(&ray, &hr));
+            } else {
+                let normal = if hr.normal.dot(&ray.direction) > 0.0 { -hr.normal } else { hr.normal };
+                let Rgb(direct) = self.direct_light(&hr.position, &normal, &|_| surface.color, true, rng);
+                for i in 0..3 {
+                    color[i] += throughput[i] * direct[i];
+                }
+
+                // Refraction adds to the diffuse light, so each is followed half of the time at twice the weight
+                let transparent = material.refractive > 1.0;
+                if transparent && rng.gen::<bool>() {
+                    let Some(refracted) = material.glossy_refracted_ray(&ray, &hr, rng.gen()) else {
+                        break;
+                    };
+                    ray = refracted;
+                    throughput = throughput.map(|t| t * 2.0);
+                } else {
+                    specular = false;
+                    sky_sampled = true;
+                    ray = Ray::normalized(hr.position + normal.into_inner() * Distance::OFF_SURFACE, cosine_direction(&normal, rng.gen()));
+                    // Cosine sampling cancels the cosine and the 1 / PI of the diffuse reflection
+                    let weight = if transparent { 2.0 } else { 1.0 };
+                    for i in 0..3 {
+                        throughput[i] *= albedo[i] * weight;
+                    }
+                }
+            }
+
+            // Going through the surface enters the object, or leaves it for the space around
+            let entering = incoming.dot(&hr.normal) < 0.0;
+            if entering == (ray.direction.dot(&hr.normal) < 0.0) {
+                medium = entering.then_some(material);
+            }
+
+            if bounce + 1 >= self.config.roulette_bounces {
+                let survival = throughput.iter().fold(0.0 as ColorPart, |a, t| a.max(*t)).clamp(0.05, 1.0);
+                if rng.gen::<ColorPart>() >= survival {
+                    break;
+                }
+                throughput = throughput.map(|t| t / survival);
+            }
+        }
+
+        Rgb(color)
+    }
+
+    /// Without highlights, the path tracer has no Blinn-Phong materials.
+    fn direct_lightning(&self, position: &Point3, normal: &UnitVector3, _view: &Vector3, _shininess: Distance, rng: &mut SmallRng) -> DirectLight {
+        DirectLight {
+            diffuse: self.direct_light(position, normal, &|_| Rgb([1.0, 1.0, 1.0]), true, rng),
+            specular: Rgb([0.0, 0.0, 0.0]),
+        }
+    }
+
+    fn reflected_lightning(&self, position: &Point3, normal: &UnitVector3, reflectance: &dyn Fn(&Vector3) -> Color, rng: &mut SmallRng) -> Color {
+        self.direct_light(position, normal, reflectance, true, rng)
+    }
+
+    /// Indirect light is traced, so there is no ambient light.
+    fn ambient_light(&self, _position: &Point3, _normal: &UnitVector3, _rng: &mut SmallRng) -> Color {
+        Rgb([0.0, 0.0, 0.0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::trace::world::{AcceleratorConfig, Environment, Light};
+    use crate::scene::material::Fresnel;
+    use crate::scene::object::Object;
+    use crate::scene::surface::Surface;
+    use crate::scene::Scene;
+    use rand::SeedableRng;
+
+    /// With nothing to bounce light back, the path tracer sees only the direct light.
+    #[test]
+    fn matches_whitted_for_direct_light() {
+        let mut scene = Scene::new();
+        let gray = Surface::new(Rgb([0.5, 0.5, 0.5]), Material::DEFAULT);
+        scene.add(Object::plane(Point3::new(0.0, -1.0, 0.0), Vector3::new(0.0, 1.0, 0.0), &gray));
+        scene.add_light(Light::new(Ray::new(Point3::new(0.0, 10.0, 0.0), Vector3::new(0.0, -1.0, 0.0)), Rgb([1.0, 1.0, 1.0])));
+        let world = World::from_scene(&scene, &AcceleratorConfig::Flat);
+
+        let ray = Ray::normalized(Point3::origin(), Vector3::new(0.3, -1.0, 0.2));
+        let mut rng = SmallRng::seed_from_u64(1);
+        let whitted = world.cast(&ray, 5, &mut rng);
+        let path = PathTracer::new(&world, PathConfig::DEFAULT).cast(&ray, 5, &mut rng);
+        assert!((whitted[0] - path[0]).abs() < 1e-6, "{:?} {:?}", whitted, path);
+    }
+
+    /// Inside a closed sphere glowing with E and reflecting a fraction a of the light, light
+    /// bounces forever and converges to E / (1 - a) everywhere.
+    #[test]
+    fn furnace_converges_to_emission_over_absorption() {
+        let mut scene = Scene::new();
+        let glowing = Surface::new(Rgb([0.5, 0.5, 0.5]), Material { emission: Rgb([0.25, 0.25, 0.25]), ..Material::DEFAULT });
+        scene.add(Object::sphere(Point3::origin(), 1.0, &glowing));
+        let world = World::from_scene(&scene, &AcceleratorConfig::Flat);
+        let tracer = PathTracer::new(&world, PathConfig::new(50, 3));
+
+        let mut rng = SmallRng::seed_from_u64(11);
+        let runs = 4000;
+        let mut sum = 0.0;
+        for _ in 0..runs {
+            let direction = Vector3::new(rng.gen::<Distance>() - 0.5, rng.gen::<Distance>() - 0.5, rng.gen::<Distance>() - 0.5);
+            sum += tracer.cast(&Ray::normalized(Point3::new(0.2, -0.1, 0.3), direction), 5, &mut rng)[0];
+        }
+        let average = sum / runs as ColorPart;
+        assert!((average - 0.5).abs() < 0.02, "{}", average);
+
+        // The same seed gives the same path
+        let ray = Ray::normalized(Point3::origin(), Vector3::new(0.3, -1.0, 0.2));
+        let first = tracer.cast(&ray, 5, &mut SmallRng::seed_from_u64(3));
+        assert_eq!(first, tracer.cast(&ray, 5, &mut SmallRng::seed_from_u64(3)));
+    }
+
+    /// Absorption follows the path through refraction, so the back of an open surface does not
+    /// take for the inside of an object.
+    #[test]
+    fn absorbs_only_inside_refracted_paths() {
+        let mut scene = Scene::new();
+        let tinted = Material { refractive: 1.5, fresnel: Fresnel::Exact, absorption: Rgb([1.0, 1.0, 1.0]), ..Material::DEFAULT };
+        let glass = Surface::new(Rgb([0.0, 0.0, 0.0]), tinted);
+        scene.add(Object::rectangle(Point3::origin(), Vector3::new(0.0, 0.0, 1.0), 10.0, 10.0, &glass));
+        scene.set_environment(Environment::Color(Rgb([1.0, 1.0, 1.0])));
+        let world = World::from_scene(&scene, &AcceleratorConfig::Flat);
+        let tracer = PathTracer::new(&world, PathConfig::DEFAULT);
+
+        // Going through the rectangle along its normal, from 5 units behind it
+        let ray = Ray::normalized(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        let mut rng = SmallRng::seed_from_u64(5);
+        for _ in 0..100 {
+            let color = tracer.cast(&ray, 5, &mut rng);
+            assert!((color[0] - 1.0).abs() < 1e-5, "{:?}", color);
+        }
+    }
+}
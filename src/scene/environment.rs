@@ -0,0 +1,219 @@
+use crate::algebra::{cosine_direction, Distance, DistanceConstants, UnitVector3, Vector3};
+use crate::scene::sky::Sky;
+use crate::scene::{Color, ColorPart};
+use image::{ImageResult, Pixel, Rgb, Rgb32FImage};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Light arriving from infinitely far away in every direction, seen by rays that miss everything.
+#[derive(Clone)]
+pub enum Environment {
+    Color(Color),
+    /// Blends from the horizon up to the zenith, and below the horizon down to the ground
+    Gradient {
+        zenith: Color,
+        horizon: Color,
+        ground: Color,
+    },
+    Map(Arc<EnvironmentMap>),
+    Sky(Sky),
+}
+
+/// Light arriving from the environment at a point, from one direction chosen at random.
+pub struct EnvironmentSample {
+    pub direction: Vector3,
+    pub color: Color, // Already divided by the probability of the direction
+}
+
+/// Equirectangular image around the scene, with the longitude along the width and the zenith
+/// at the top. The middle of the image is in the +z direction.
+pub struct EnvironmentMap {
+    image: Rgb32FImage,
+    intensity: ColorPart,
+    rotation: Distance, // Radians around the y axis
+    rows: Cdf,          // Brightness of whole rows
+    columns: Vec<Cdf>,  // Brightness within each row
+}
+
+/// Cumulative distribution over the cells of a piecewise constant function.
+struct Cdf {
+    cumulative: Vec<Distance>,
+}
+
+const BLACK: Color = Rgb([0.0, 0.0, 0.0]);
+
+impl Cdf {
+    fn new(weights: impl Iterator<Item = Distance>) -> Self {
+        let mut total = 0.0;
+        let cumulative = weights
+            .map(|w| {
+                total += w;
+                total
+            })
+            .collect();
+        Self { cumulative }
+    }
+
+    fn total(&self) -> Distance {
+        self.cumulative.last().copied().unwrap_or(0.0)
+    }
+
+    /// Cell chosen by `u` in proportion to its weight, the probability of choosing it, and
+    /// where `u` falls within the cell.
+    fn sample(&self, u: Distance) -> (usize, Distance, Distance) {
+        let target = u * self.total();
+        let cell = self.cumulative.partition_point(|c| *c <= target).min(self.cumulative.len() - 1);
+        let start = if cell == 0 { 0.0 } else { self.cumulative[cell - 1] };
+        let weight = self.cumulative[cell] - start;
+        (cell, weight / self.total(), ((target - start) / weight).clamp(0.0, 1.0))
+    }
+}
+
+fn luminance(Rgb([r, g, b]): Color) -> Distance {
+    (0.2126 * r + 0.7152 * g + 0.0722 * b) as Distance
+}
+
+impl EnvironmentMap {
+    /// Loads an equirectangular image, usually in a high dynamic range format such as .hdr or .exr.
+    pub fn load(path: &Path, intensity: ColorPart, rotation: Distance) -> ImageResult<Self> {
+        Ok(Self::new(image::open(path)?.into_rgb32f(), intensity, rotation.to_radians()))
+    }
+
+    pub fn new(image: Rgb32FImage, intensity: ColorPart, rotation: Distance) -> Self {
+        let (width, height) = image.dimensions();
+
+        // Rows near the poles cover less of the sphere
+        let columns: Vec<Cdf> = (0..height)
+            .map(|y| {
+                let sin_theta = ((y as Distance + 0.5) / height as Distance * Distance::PI).sin();
+                Cdf::new((0..width).map(|x| luminance(*image.get_pixel(x, y)) * sin_theta))
+            })
+            .collect();
+        let rows = Cdf::new(columns.iter().map(Cdf::total));
+
+        Self { image, intensity, rotation, rows, columns }
+    }
+
+    /// Position in the image, in the unit square.
+    fn coords(&self, direction: &Vector3) -> (Distance, Distance) {
+        let direction = direction.normalize();
+        let longitude = direction.x.atan2(direction.z) - self.rotation;
+        let u = (longitude / (2.0 * Distance::PI) + 0.5).rem_euclid(1.0);
+        let v = 0.5 - direction.y.clamp(-1.0, 1.0).asin() / Distance::PI;
+        (u, v)
+    }
+
+    fn direction(&self, (u, v): (Distance, Distance)) -> Vector3 {
+        let longitude = (u - 0.5) * 2.0 * Distance::PI + self.rotation;
+        let latitude = (0.5 - v) * Distance::PI;
+        Vector3::new(latitude.cos() * longitude.sin(), latitude.sin(), latitude.cos() * longitude.cos())
+    }
+
+    fn pixel(&self, (u, v): (Distance, Distance)) -> Color {
+        let (width, height) = self.image.dimensions();
+        let x = ((u * width as Distance) as u32).min(width - 1);
+        let y = ((v * height as Distance) as u32).min(height - 1);
+        self.image.get_pixel(x, y).map(|c| c * self.intensity)
+    }
+
+    pub fn radiance(&self, direction: &Vector3) -> Color {
+        self.pixel(self.coords(direction))
+    }
+
+    /// Direction chosen in proportion to the brightness of the image.
+    fn sample(&self, normal: &UnitVector3, u: (Distance, Distance)) -> Option<EnvironmentSample> {
+        if self.rows.total() <= 0.0 {
+            return None;
+        }
+
+        let (width, height) = self.image.dimensions();
+        let (y, row_probability, v_within) = self.rows.sample(u.1);
+        let (x, column_probability, u_within) = self.columns[y].sample(u.0);
+
+        // Anywhere within the pixel, which has the same probability everywhere
+        let coords = ((x as Distance + u_within) / width as Distance, (y as Distance + v_within) / height as Distance);
+        let direction = self.direction(coords);
+        let cos_receiver = direction.dot(normal);
+        if cos_receiver <= 0.0 {
+            return None;
+        }
+
+        // From the area of the image to the solid angle it covers
+        let sin_theta = (coords.1 * Distance::PI).sin();
+        let density = row_probability * column_probability * (width * height) as Distance
+            / (2.0 * Distance::PI * Distance::PI * sin_theta);
+
+        // With the 1 / PI of diffuse surfaces, as for area lights
+        let strength = (cos_receiver / (Distance::PI * density)) as ColorPart;
+        Some(EnvironmentSample {
+            direction,
+            color: self.pixel(coords).map(|c| c * strength),
+        })
+    }
+}
+
+impl Environment {
+    pub const BLACK: Environment = Environment::Color(BLACK);
+
+    pub fn radiance(&self, direction: &Vector3) -> Color {
+        match self {
+            Environment::Color(color) => *color,
+            Environment::Gradient { zenith, horizon, ground } => {
+                let height = direction.normalize().y as ColorPart;
+                let (end, t) = if height >= 0.0 { (zenith, height) } else { (ground, -height) };
+                Rgb([0, 1, 2].map(|i| horizon[i] + (end[i] - horizon[i]) * t))
+            }
+            Environment::Map(map) => map.radiance(direction),
+            Environment::Sky(sky) => sky.radiance(direction),
+        }
+    }
+
+    pub fn is_black(&self) -> bool {
+        matches!(self, Environment::Color(color) if *color == BLACK)
+    }
+
+    /// Light arriving at a surface with the given normal from a direction chosen by `u` in the
+    /// unit square, None if nothing arrives. Maps favour their bright parts, the others the
+    /// directions close to the normal.
+    pub fn sample(&self, normal: &UnitVector3, u: (Distance, Distance)) -> Option<EnvironmentSample> {
+        match self {
+            Environment::Map(map) => map.sample(normal, u),
+            _ if self.is_black() => None,
+            _ => {
+                // The cosine and the 1 / PI are cancelled by the probability of the direction
+                let direction = cosine_direction(normal, u);
+                Some(EnvironmentSample { direction, color: self.radiance(&direction) })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::ImageBuffer;
+
+    /// A map of a single color lights a surface as much as that constant color does.
+    #[test]
+    fn uniform_map_matches_color() {
+        let image: Rgb32FImage = ImageBuffer::from_pixel(16, 8, Rgb([0.5, 0.5, 0.5]));
+        let map = Environment::Map(Arc::new(EnvironmentMap::new(image, 2.0, 0.0)));
+        let normal = UnitVector3::new_normalize(Vector3::new(0.3, 1.0, -0.2));
+
+        let samples = 64;
+        let mut sum = 0.0;
+        for i in 0..samples {
+            for j in 0..samples {
+                let u = ((i as Distance + 0.5) / samples as Distance, (j as Distance + 0.5) / samples as Distance);
+                if let Some(sample) = map.sample(&normal, u) {
+                    sum += sample.color[0];
+                }
+            }
+        }
+        let average = sum / (samples * samples) as ColorPart;
+        assert!((average - 1.0).abs() < 0.05, "{}", average);
+
+        let up = Vector3::new(0.0, 1.0, 0.0);
+        assert_eq!(map.radiance(&up), Rgb([1.0, 1.0, 1.0]));
+    }
+}
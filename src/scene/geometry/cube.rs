@@ -68,6 +68,29 @@ impl Geometry for Cube {
             coords: (0.0, 0.0)
         }
     }
+
+    fn sample(&self, u: (Distance, Distance)) -> Option<HitResult> {
+        // One of the six faces, then a point on it
+        let scaled = u.0 * 6.0;
+        let face = (scaled as usize).min(5);
+        let (a, b) = (scaled - face as Distance - 0.5, u.1 - 0.5);
+        let side = [0.5, -0.5][face % 2];
+        let (position, normal) = match face / 2 {
+            0 => (Point3::new(side, a, b), Vector3::new(side * 2.0, 0.0, 0.0)),
+            1 => (Point3::new(a, side, b), Vector3::new(0.0, side * 2.0, 0.0)),
+            _ => (Point3::new(a, b, side), Vector3::new(0.0, 0.0, side * 2.0)),
+        };
+
+        Some(HitResult {
+            position,
+            normal: UnitVector3::new_unchecked(normal),
+            coords: (0.0, 0.0),
+        })
+    }
+
+    fn area(&self) -> Distance {
+        6.0
+    }
 }
 
 #[cfg(test)]
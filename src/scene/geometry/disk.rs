@@ -0,0 +1,58 @@
+use crate::algebra::{Bounded, BoundingBox, Distance, DistanceConstants, Point3, Ray};
+use crate::scene::geometry::{Geometry, HitResult, Plane};
+
+/// Disk of radius 1 around the origin in the xz plane, facing up.
+pub struct Disk {
+}
+
+impl Disk {
+    pub fn new() -> Self {
+        Self {
+        }
+    }
+
+    fn hit_at(position: Point3) -> HitResult {
+        HitResult {
+            position,
+            normal: Plane::NORMAL,
+            coords: ((position.x + 1.0) / 2.0, (position.z + 1.0) / 2.0),
+        }
+    }
+}
+
+impl Bounded for Disk {
+    fn bounding_box(&self) -> BoundingBox {
+        BoundingBox::new(Point3::new(-1.0, 0.0, -1.0), Point3::new(1.0, 0.0, 1.0))
+    }
+}
+
+impl Geometry for Disk {
+    fn distance(&self, ray: &Ray) -> Option<Distance> {
+        let denom = ray.direction.y;
+        if denom.abs() < 1e-12 {
+            return None;
+        }
+
+        let t = -ray.origin.y / denom;
+        if t <= 0.0 {
+            return None;
+        }
+
+        let position = ray.at(t);
+        (position.x * position.x + position.z * position.z <= 1.0).then_some(t)
+    }
+
+    fn hit(&self, ray: &Ray, distance: Distance) -> HitResult {
+        Disk::hit_at(ray.at(distance))
+    }
+
+    fn sample(&self, u: (Distance, Distance)) -> Option<HitResult> {
+        let radius = u.0.sqrt();
+        let angle = 2.0 * Distance::PI * u.1;
+        Some(Disk::hit_at(Point3::new(radius * angle.cos(), 0.0, radius * angle.sin())))
+    }
+
+    fn area(&self) -> Distance {
+        Distance::PI
+    }
+}
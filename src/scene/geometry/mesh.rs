@@ -0,0 +1,261 @@
+use crate::algebra::{Bounded, BoundingBox, BuildStats, Bvh, BvhConfig, Distance, Point3, Ray, UnitVector3, Vector3};
+use crate::scene::geometry::{Geometry, HitResult, TextureCoords};
+
+/// A triangle referencing the vertex attributes of its mesh by index.
+#[derive(Debug, Copy, Clone)]
+pub struct Triangle {
+    pub positions: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub coords: Option<[usize; 3]>,
+}
+
+/// Where a ray hit a triangle, with barycentric coordinates of the second and third vertex.
+#[derive(Debug, Copy, Clone)]
+struct TriangleHit {
+    triangle: usize,
+    distance: Distance,
+    u: Distance,
+    v: Distance,
+}
+
+pub struct TriangleMesh {
+    positions: Vec<Point3>,
+    normals: Vec<Vector3>,
+    coords: Vec<TextureCoords>,
+    triangles: Vec<Triangle>,
+    bvh: Bvh,
+    areas: Vec<Distance>, // Cumulative triangle areas, for sampling the surface
+}
+
+impl TriangleMesh {
+    pub fn new(positions: Vec<Point3>, normals: Vec<Vector3>, coords: Vec<TextureCoords>, triangles: Vec<Triangle>) -> Self {
+        let bounds: Vec<BoundingBox> = triangles
+            .iter()
+            .map(|t| t.positions.iter().fold(BoundingBox::empty(), |b, i| b.include(&positions[*i])))
+            .collect();
+        let bvh = Bvh::build(&bounds, &BvhConfig::DEFAULT);
+
+        let areas = triangles
+            .iter()
+            .scan(0.0, |total, t| {
+                let [a, b, c] = t.positions.map(|i| positions[i]);
+                *total += (b - a).cross(&(c - a)).magnitude() / 2.0;
+                Some(*total)
+            })
+            .collect();
+
+        Self {
+            positions,
+            normals,
+            coords,
+            triangles,
+            bvh,
+            areas,
+        }
+    }
+
+    /// Shape of the hierarchy, to tune it.
+    #[allow(dead_code)]
+    pub fn build_stats(&self) -> &BuildStats {
+        self.bvh.build_stats()
+    }
+
+    /// Möller–Trumbore ray/triangle intersection.
+    fn intersect_triangle(&self, index: usize, ray: &Ray, max: Distance) -> Option<TriangleHit> {
+        const EPSILON: Distance = 1e-12;
+
+        let [a, b, c] = self.triangles[index].positions.map(|i| self.positions[i]);
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let p = ray.direction.cross(&edge2);
+        let det = edge1.dot(&p);
+        if det.abs() < EPSILON {
+            return None; // Parallel to the triangle
+        }
+
+        let inv_det = 1.0 / det;
+        let s = ray.origin - a;
+        let u = s.dot(&p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(&edge1);
+        let v = ray.direction.dot(&q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let distance = edge2.dot(&q) * inv_det;
+        if distance <= 0.0 || distance >= max {
+            return None;
+        }
+
+        Some(TriangleHit { triangle: index, distance, u, v })
+    }
+
+    fn closest_hit(&self, ray: &Ray, max: Distance) -> Option<TriangleHit> {
+        self.bvh.closest(ray, max, |index, max| {
+            self.intersect_triangle(index, ray, max).map(|hit| (hit.distance, hit))
+        })
+    }
+
+    fn interpolate<T>(values: [T; 3], u: Distance, v: Distance) -> T
+    where
+        T: std::ops::Mul<Distance, Output = T> + std::ops::Add<Output = T>,
+    {
+        let [a, b, c] = values;
+        a * (1.0 - u - v) + b * u + c * v
+    }
+
+    fn hit_result(&self, position: Point3, hit: &TriangleHit) -> HitResult {
+        let triangle = &self.triangles[hit.triangle];
+
+        let normal = match triangle.normals {
+            Some(normals) => Self::interpolate(normals.map(|i| self.normals[i]), hit.u, hit.v),
+            None => {
+                let [a, b, c] = triangle.positions.map(|i| self.positions[i]);
+                (b - a).cross(&(c - a))
+            }
+        };
+
+        let coords = match triangle.coords {
+            Some(coords) => {
+                let [a, b, c] = coords.map(|i| self.coords[i]);
+                (
+                    Self::interpolate([a.0, b.0, c.0], hit.u, hit.v),
+                    Self::interpolate([a.1, b.1, c.1], hit.u, hit.v),
+                )
+            }
+            None => (hit.u, hit.v),
+        };
+
+        HitResult {
+            position,
+            normal: UnitVector3::new_normalize(normal),
+            coords,
+        }
+    }
+}
+
+impl Bounded for TriangleMesh {
+    fn bounding_box(&self) -> BoundingBox {
+        self.bvh.bounding_box()
+    }
+}
+
+impl Geometry for TriangleMesh {
+    fn distance(&self, ray: &Ray) -> Option<Distance> {
+        self.closest_hit(ray, Distance::INFINITY).map(|hit| hit.distance)
+    }
+
+    fn hit(&self, ray: &Ray, distance: Distance) -> HitResult {
+        // Find the triangle again, allowing for rounding of the distance. The ray is the one
+        // `distance` was given, so it cannot miss
+        let hit = self
+            .closest_hit(ray, distance * (1.0 + 1e-9) + 1e-12)
+            .expect("ray misses the mesh at the distance where it hit it");
+        self.hit_result(ray.at(hit.distance), &hit)
+    }
+
+    fn sample(&self, u: (Distance, Distance)) -> Option<HitResult> {
+        if self.triangles.is_empty() {
+            return None;
+        }
+
+        // A triangle in proportion to its area, reusing what is left of `u.0` within it
+        let target = u.0 * self.area();
+        let triangle = self.areas.partition_point(|a| *a < target).min(self.triangles.len() - 1);
+        let start = if triangle == 0 { 0.0 } else { self.areas[triangle - 1] };
+        let width = self.areas[triangle] - start;
+        let first = if width > 0.0 { ((target - start) / width).clamp(0.0, 1.0) } else { 0.5 };
+
+        // Uniform barycentric coordinates
+        let root = first.sqrt();
+        let hit = TriangleHit { triangle, distance: 0.0, u: root * (1.0 - u.1), v: root * u.1 };
+        let [a, b, c] = self.triangles[triangle].positions.map(|i| self.positions[i]);
+        let position = Point3::from(Self::interpolate([a.coords, b.coords, c.coords], hit.u, hit.v));
+        Some(self.hit_result(position, &hit))
+    }
+
+    fn area(&self) -> Distance {
+        self.areas.last().copied().unwrap_or(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hit_interpolates_coords() {
+        let mesh = TriangleMesh::new(
+            vec![Point3::new(0.0, 0.0, 1.0), Point3::new(1.0, 0.0, 1.0), Point3::new(0.0, 1.0, 1.0)],
+            vec![],
+            vec![(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+            vec![Triangle { positions: [0, 1, 2], normals: None, coords: Some([0, 1, 2]) }],
+        );
+
+        let ray = Ray::new(Point3::new(0.25, 0.5, 0.0), Vector3::new(0.0, 0.0, 1.0));
+        let distance = mesh.distance(&ray).unwrap();
+        assert_eq!(distance, 1.0);
+
+        let hr = mesh.hit(&ray, distance);
+        assert!((hr.coords.0 - 0.25).abs() < 1e-9 && (hr.coords.1 - 0.5).abs() < 1e-9);
+        assert_eq!(hr.normal.into_inner(), Vector3::new(0.0, 0.0, 1.0));
+    }
+
+    /// Compares the hierarchy against testing every triangle, on a finely tessellated sphere.
+    #[test]
+    #[ignore]
+    fn bench() {
+        use std::time::Instant;
+
+        let (rings, segments) = (500, 1000);
+        let mut positions = vec![];
+        for ring in 0..=rings {
+            let theta = ring as Distance / rings as Distance * std::f64::consts::PI;
+            for segment in 0..segments {
+                let phi = segment as Distance / segments as Distance * 2.0 * std::f64::consts::PI;
+                positions.push(Point3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin()));
+            }
+        }
+        let mut triangles = vec![];
+        for ring in 0..rings {
+            for segment in 0..segments {
+                let a = ring * segments + segment;
+                let b = ring * segments + (segment + 1) % segments;
+                let (c, d) = (a + segments, b + segments);
+                triangles.push(Triangle { positions: [a, c, b], normals: None, coords: None });
+                triangles.push(Triangle { positions: [b, c, d], normals: None, coords: None });
+            }
+        }
+
+        let mesh = TriangleMesh::new(positions, vec![], vec![], triangles);
+        println!("{}", mesh.build_stats());
+
+        let rays: Vec<Ray> = (0..1000)
+            .map(|i| Ray::normalized(Point3::new(0.0, 0.0, -5.0), Vector3::new((i % 40) as Distance / 40.0 - 0.5, (i / 40) as Distance / 25.0 - 0.5, 2.0)))
+            .collect();
+
+        let time = Instant::now();
+        let mut stats = Default::default();
+        for ray in rays.iter() {
+            mesh.bvh.closest_counted(ray, Distance::INFINITY, &mut stats, |index, max| {
+                mesh.intersect_triangle(index, ray, max).map(|hit| (hit.distance, hit))
+            });
+        }
+        println!("bvh: {:?}, {}", time.elapsed(), stats);
+
+        let time = Instant::now();
+        for ray in rays.iter() {
+            let mut shortest = Distance::INFINITY;
+            for index in 0..mesh.triangles.len() {
+                if let Some(hit) = mesh.intersect_triangle(index, ray, shortest) {
+                    shortest = hit.distance;
+                }
+            }
+        }
+        println!("brute force: {:?}, {} triangles per ray", time.elapsed(), mesh.triangles.len());
+    }
+}
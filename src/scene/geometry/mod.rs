@@ -1,12 +1,18 @@
 use crate::algebra::Ray;
 use crate::algebra::{Bounded, Distance, Point3, UnitVector3};
 pub use cube::Cube;
+pub use disk::Disk;
+pub use mesh::{Triangle, TriangleMesh};
 pub use plane::Plane;
+pub use rectangle::Rectangle;
 pub use sphere::Sphere;
 
 mod sphere;
 mod cube;
 mod plane;
+mod mesh;
+mod rectangle;
+mod disk;
 
 pub type TextureCoords = (Distance, Distance);
 
@@ -22,4 +28,26 @@ pub trait Geometry: Send + Sync + Bounded {
     fn distance(&self, ray: &Ray) -> Option<Distance>;
 
     fn hit(&self, ray: &Ray, distance: Distance) -> HitResult;
+
+    /// Point on the surface for `u` in the unit square, uniformly distributed over the surface.
+    /// None for geometry that cannot be sampled as a light.
+    fn sample(&self, _u: (Distance, Distance)) -> Option<HitResult> {
+        None
+    }
+
+    /// Area of the surface covered by `sample`.
+    fn area(&self) -> Distance {
+        Distance::INFINITY
+    }
+
+    /// Whether `sample` gives points, so that the geometry can be the shape of a light.
+    fn can_sample(&self) -> bool {
+        self.area().is_finite() && self.area() > 0.0
+    }
+
+    /// Point on the surface for `u` in the unit square, uniformly distributed over the part that
+    /// may be seen from `from`, together with the area of that part.
+    fn sample_from(&self, _from: &Point3, u: (Distance, Distance)) -> Option<(HitResult, Distance)> {
+        Some((self.sample(u)?, self.area()))
+    }
 }
@@ -0,0 +1,56 @@
+use crate::algebra::{Bounded, BoundingBox, Distance, Point3, Ray};
+use crate::scene::geometry::{Geometry, HitResult, Plane};
+
+/// Square from -1 to 1 in x and z, facing up like the plane it is cut from.
+pub struct Rectangle {
+}
+
+impl Rectangle {
+    pub fn new() -> Self {
+        Self {
+        }
+    }
+
+    fn hit_at(position: Point3) -> HitResult {
+        HitResult {
+            position,
+            normal: Plane::NORMAL,
+            coords: ((position.x + 1.0) / 2.0, (position.z + 1.0) / 2.0),
+        }
+    }
+}
+
+impl Bounded for Rectangle {
+    fn bounding_box(&self) -> BoundingBox {
+        BoundingBox::new(Point3::new(-1.0, 0.0, -1.0), Point3::new(1.0, 0.0, 1.0))
+    }
+}
+
+impl Geometry for Rectangle {
+    fn distance(&self, ray: &Ray) -> Option<Distance> {
+        let denom = ray.direction.y;
+        if denom.abs() < 1e-12 {
+            return None;
+        }
+
+        let t = -ray.origin.y / denom;
+        if t <= 0.0 {
+            return None;
+        }
+
+        let position = ray.at(t);
+        (position.x.abs() <= 1.0 && position.z.abs() <= 1.0).then_some(t)
+    }
+
+    fn hit(&self, ray: &Ray, distance: Distance) -> HitResult {
+        Rectangle::hit_at(ray.at(distance))
+    }
+
+    fn sample(&self, u: (Distance, Distance)) -> Option<HitResult> {
+        Some(Rectangle::hit_at(Point3::new(u.0 * 2.0 - 1.0, 0.0, u.1 * 2.0 - 1.0)))
+    }
+
+    fn area(&self) -> Distance {
+        4.0
+    }
+}
@@ -1,4 +1,4 @@
-use crate::algebra::{Distance, DistanceConstants, Ray, UnitVector3};
+use crate::algebra::{Distance, DistanceConstants, Ray, UnitVector3, VectorOps};
 use crate::algebra::{Bounded, BoundingBox, Point3};
 use crate::scene::geometry::{Geometry, HitResult, TextureCoords};
 
@@ -74,6 +74,49 @@ impl Geometry for Sphere {
             coords: self.texture_coords(&position),
         }
     }
+
+    fn sample(&self, u: (Distance, Distance)) -> Option<HitResult> {
+        let y = 1.0 - 2.0 * u.0;
+        let radius = (1.0 - y * y).max(0.0).sqrt();
+        let angle = 2.0 * Distance::PI * u.1;
+        let position = Point3::new(radius * angle.cos(), y, radius * angle.sin());
+        Some(HitResult {
+            position,
+            normal: UnitVector3::new_unchecked(position.coords),
+            coords: self.texture_coords(&position),
+        })
+    }
+
+    fn area(&self) -> Distance {
+        4.0 * Distance::PI
+    }
+
+    fn sample_from(&self, from: &Point3, u: (Distance, Distance)) -> Option<(HitResult, Distance)> {
+        let distance = from.coords.magnitude();
+        if distance <= 1.0 {
+            // Inside, the whole sphere is in sight
+            return Some((self.sample(u)?, self.area()));
+        }
+
+        // Cap around the direction of `from`, bounded where the lines of sight graze the sphere
+        let axis = from.coords / distance;
+        let cos_max = 1.0 / distance;
+        let tangent = axis.perpendicular();
+        let bitangent = axis.cross(&tangent);
+
+        let z = 1.0 - u.0 * (1.0 - cos_max);
+        let radius = (1.0 - z * z).max(0.0).sqrt();
+        let angle = 2.0 * Distance::PI * u.1;
+        let position = Point3::from(axis * z + (tangent * angle.cos() + bitangent * angle.sin()) * radius);
+        Some((
+            HitResult {
+                position,
+                normal: UnitVector3::new_unchecked(position.coords),
+                coords: self.texture_coords(&position),
+            },
+            2.0 * Distance::PI * (1.0 - cos_max),
+        ))
+    }
 }
 
 pub struct Sphere {}
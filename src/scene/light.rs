@@ -1,24 +1,143 @@
 use crate::algebra::Ray;
-use crate::algebra::{Point3, UnitVector3, Vector3, VectorOps};
+use crate::algebra::{Distance, DistanceConstants, Point3, UnitVector3, Vector3, VectorOps};
+use crate::scene::material::Material;
+use crate::scene::object::Object;
+use crate::scene::surface::Surface;
 use crate::scene::{Color, ColorPart};
 use image::{Pixel, Rgb};
+use serde::Deserialize;
+use std::fmt;
+use std::sync::Arc;
 
+/// How the light weakens with the distance from it.
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Falloff {
+    /// Same brightness at any distance
+    None,
+    /// Divided by the distance
+    Linear,
+    /// Divided by the square of the distance, as real point lights
+    InverseSquare,
+}
+
+/// Cone of a spotlight around the light direction, with angles in degrees from its axis.
+/// Full brightness within the inner angle, fading smoothly to nothing at the outer angle.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Spot {
+    pub inner: Distance,
+    pub outer: Distance,
+}
+
+impl Spot {
+    pub fn new(inner: Distance, outer: Distance) -> Self {
+        Self { inner, outer: outer.max(inner) }
+    }
+
+    /// Fraction of the light sent in a direction at `cos_angle` from the axis.
+    fn strength(&self, cos_angle: Distance) -> ColorPart {
+        let cos_inner = self.inner.to_radians().cos();
+        let cos_outer = self.outer.to_radians().cos();
+        if cos_angle >= cos_inner {
+            return 1.0;
+        }
+        if cos_angle <= cos_outer {
+            return 0.0;
+        }
+
+        let t = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+        (t * t * (3.0 - 2.0 * t)) as ColorPart
+    }
+}
+
+/// Light sent in all directions from a point, or within a cone for a spotlight.
 #[derive(Debug, Copy, Clone)]
-pub struct Light {
+pub struct PointLight {
     pub ray: Ray,
     pub color: Color,
+    pub intensity: ColorPart, // Scales the color, at a distance of one with falloff
+    pub falloff: Falloff,
+    pub range: Option<Distance>, // Distance where the light smoothly fades out to nothing
+    pub spot: Option<Spot>, // Limits the light to a cone along the ray direction
 }
 
-impl Light {
-    const BLACK: Color = Rgb([0.0, 0.0, 0.0]);
-    
+/// Light from infinitely far away, such as the sun, arriving everywhere from the same direction.
+#[derive(Debug, Copy, Clone)]
+pub struct DirectionalLight {
+    pub direction: UnitVector3, // Where the light travels
+    pub color: Color,
+    pub intensity: ColorPart,
+}
+
+/// Emissive object lighting the scene from its whole surface, which gives soft shadows.
+/// The light is the emission of the material where the surface is sampled.
+#[derive(Clone)]
+pub struct AreaLight {
+    pub object: Arc<Object>, // Seen by camera rays, and sampled by shadow rays
+    pub samples: u32, // Shadow rays for each shaded point
+}
+
+impl fmt::Debug for AreaLight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "AreaLight {{ samples: {} }}", self.samples)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Light {
+    Point(PointLight),
+    Directional(DirectionalLight),
+    Area(AreaLight),
+}
+
+/// Rays cast over the hemisphere of a point to find how much of the ambient light reaches it.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Occlusion {
+    pub samples: u32,
+    pub distance: Distance, // Anything farther does not occlude
+}
+
+/// Constant light added to every diffuse surface, so that sides facing away from the lights
+/// are not black. Ambient occlusion darkens it in corners and crevices.
+#[derive(Debug, Copy, Clone)]
+pub struct Ambient {
+    pub color: Color,
+    pub occlusion: Option<Occlusion>,
+}
+
+/// Light arriving at a point from one position on a light.
+pub struct LightSample {
+    pub color: Color,
+    pub shadow_ray: Ray,
+    pub distance: Distance, // Along the shadow ray, anything closer casts a shadow
+}
+
+const BLACK: Color = Rgb([0.0, 0.0, 0.0]);
+
+impl PointLight {
     pub fn new(ray: Ray, color: Color) -> Self {
-        Light {
+        PointLight {
             ray,
-            color
+            color,
+            intensity: 1.0,
+            falloff: Falloff::None,
+            range: None,
+            spot: None,
         }
     }
-    
+
+    pub fn with_falloff(self, intensity: ColorPart, falloff: Falloff) -> Self {
+        Self { intensity, falloff, ..self }
+    }
+
+    pub fn with_range(self, range: Distance) -> Self {
+        Self { range: Some(range), ..self }
+    }
+
+    pub fn with_spot(self, spot: Spot) -> Self {
+        Self { spot: Some(spot), ..self }
+    }
+
     pub fn towards(&self, position: Point3) -> Ray {
         Ray::new(position, self.towards_direction(position))
     }
@@ -26,15 +145,222 @@ impl Light {
     pub fn towards_direction(&self, position: Point3) -> Vector3 {
         self.ray.origin - position
     }
-    
+
+    /// Fraction of the intensity left at a distance from the light.
+    pub fn attenuation(&self, distance: Distance) -> ColorPart {
+        let falloff = match self.falloff {
+            Falloff::None => 1.0,
+            Falloff::Linear => 1.0 / distance.max(Distance::EPSILON),
+            Falloff::InverseSquare => 1.0 / (distance * distance).max(Distance::EPSILON),
+        };
+
+        // Windowed to reach zero at the range without a visible edge
+        let window = match self.range {
+            Some(range) => {
+                let ratio = distance / range;
+                (1.0 - ratio.powi(4)).max(0.0).powi(2)
+            }
+            None => 1.0,
+        };
+
+        (falloff * window) as ColorPart
+    }
+
     pub fn illuminate(&self, position: Point3, normal: UnitVector3) -> Color {
         let direction_to_light = self.towards_direction(position);
         let fraction = direction_to_light.cos_angle(&normal) as ColorPart;
-        
+
         if fraction <= 0.0 {
-            return Self::BLACK;
+            return BLACK;
+        }
+
+        let cone = match self.spot {
+            Some(spot) => spot.strength(-direction_to_light.cos_angle(&self.ray.direction)),
+            None => 1.0,
+        };
+
+        let strength = fraction * cone * self.intensity * self.attenuation(direction_to_light.magnitude());
+        self.color.map(|c| c * strength)
+    }
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vector3, color: Color) -> Self {
+        Self {
+            direction: UnitVector3::new_normalize(direction),
+            color,
+            intensity: 1.0,
         }
-        
-        self.color.map(|c|c * fraction)
     }
-}
\ No newline at end of file
+
+    pub fn with_intensity(self, intensity: ColorPart) -> Self {
+        Self { intensity, ..self }
+    }
+
+    pub fn illuminate(&self, normal: UnitVector3) -> Color {
+        let fraction = -self.direction.dot(&normal) as ColorPart;
+        if fraction <= 0.0 {
+            return BLACK;
+        }
+
+        self.color.map(|c| c * fraction * self.intensity)
+    }
+}
+
+impl AreaLight {
+    pub const DEFAULT_SAMPLES: u32 = 16;
+
+    /// Black surface glowing with `radiance`, for objects that are only lights.
+    pub fn surface(radiance: Color) -> Surface {
+        Surface::new(BLACK, Material { emission: radiance, ..Material::DEFAULT })
+    }
+
+    /// The geometry of the object must support sampling.
+    pub fn new(object: Arc<Object>, samples: u32) -> Self {
+        Self {
+            object,
+            samples: samples.max(1),
+        }
+    }
+
+    fn sample(&self, position: Point3, normal: UnitVector3, u: (Distance, Distance)) -> Option<LightSample> {
+        let surface = self.object.sample_surface(&position, u)?;
+        let towards = surface.position - position;
+        let distance_squared = towards.magnitude_squared();
+
+        let cos_receiver = towards.cos_angle(&normal);
+        let cos_emitter = -towards.dot(&surface.normal) / distance_squared.sqrt();
+        if cos_receiver <= 0.0 || cos_emitter <= 0.0 {
+            return None;
+        }
+
+        // Radiance over the solid angle of the sample, with the 1 / PI of diffuse surfaces
+        // that point lights leave out
+        let strength = (cos_receiver * cos_emitter * surface.area / (Distance::PI * distance_squared)) as ColorPart;
+        let radiance = self.object.texture.surface_at(surface.coords).material.emission;
+        Some(LightSample {
+            color: radiance.map(|c| c * strength),
+            shadow_ray: Ray::new(position, towards),
+            distance: 1.0 - 1e-4, // Stop short of the light itself
+        })
+    }
+}
+
+impl Ambient {
+    pub const NONE: Ambient = Ambient { color: BLACK, occlusion: None };
+
+    pub fn new(color: Color) -> Self {
+        Self { color, occlusion: None }
+    }
+
+    pub fn with_occlusion(self, samples: u32, distance: Distance) -> Self {
+        Self {
+            occlusion: Some(Occlusion { samples: samples.max(1), distance }),
+            ..self
+        }
+    }
+}
+
+impl Light {
+    /// Point light at the origin of `ray`, shining along its direction if it is made a spotlight.
+    pub fn new(ray: Ray, color: Color) -> Self {
+        Light::Point(PointLight::new(ray, color))
+    }
+
+    /// Shadow rays used for each shaded point.
+    pub fn sample_count(&self) -> u32 {
+        match self {
+            Light::Area(light) => light.samples,
+            _ => 1,
+        }
+    }
+
+    /// Light arriving at a position from the point of the light chosen by `u` in the unit
+    /// square, None if no light arrives. Only area lights use `u`.
+    pub fn sample(&self, position: Point3, normal: UnitVector3, u: (Distance, Distance)) -> Option<LightSample> {
+        let (color, shadow_ray, distance) = match self {
+            // Not normalized, the light is at a distance of one
+            Light::Point(light) => (light.illuminate(position, normal), light.towards(position), 1.0),
+            Light::Directional(light) => (light.illuminate(normal), Ray::new(position, -light.direction.into_inner()), Distance::INFINITY),
+            Light::Area(light) => return light.sample(position, normal, u),
+        };
+
+        (color != BLACK).then_some(LightSample { color, shadow_ray, distance })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falloff_with_distance() {
+        let light = PointLight::new(Ray::new(Point3::origin(), Vector3::new(0.0, -1.0, 0.0)), Rgb([1.0, 1.0, 1.0]));
+        assert_eq!(light.attenuation(10.0), 1.0);
+        assert_eq!(light.with_falloff(1.0, Falloff::Linear).attenuation(10.0), 0.1);
+        assert!((light.with_falloff(1.0, Falloff::InverseSquare).attenuation(10.0) - 0.01).abs() < 1e-9);
+
+        let ranged = light.with_range(20.0);
+        assert!(ranged.attenuation(1.0) > 0.99);
+        assert_eq!(ranged.attenuation(20.0), 0.0);
+        assert_eq!(ranged.attenuation(30.0), 0.0);
+    }
+
+    #[test]
+    fn spot_cone() {
+        let light = PointLight::new(Ray::new(Point3::origin(), Vector3::new(0.0, -1.0, 0.0)), Rgb([1.0, 1.0, 1.0]))
+            .with_spot(Spot::new(10.0, 20.0));
+        let up = UnitVector3::new_normalize(Vector3::new(0.0, 1.0, 0.0));
+
+        assert_eq!(light.illuminate(Point3::new(0.0, -10.0, 0.0), up), Rgb([1.0, 1.0, 1.0]));
+        let edge = light.illuminate(Point3::new(10.0 * 15.0f64.to_radians().tan(), -10.0, 0.0), up);
+        assert!(edge[0] > 0.0 && edge[0] < 1.0);
+        assert_eq!(light.illuminate(Point3::new(10.0, -10.0, 0.0), up), Rgb([0.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn directional_light_is_parallel() {
+        let light = Light::Directional(DirectionalLight::new(Vector3::new(0.0, -1.0, 0.0), Rgb([1.0, 1.0, 1.0])));
+        let up = UnitVector3::new_normalize(Vector3::new(0.0, 1.0, 0.0));
+
+        for position in [Point3::origin(), Point3::new(1000.0, -50.0, 3.0)] {
+            let sample = light.sample(position, up, (0.5, 0.5)).unwrap();
+            assert_eq!(sample.color, Rgb([1.0, 1.0, 1.0]));
+            assert_eq!(sample.shadow_ray.direction, Vector3::new(0.0, 1.0, 0.0));
+            assert_eq!(sample.distance, Distance::INFINITY);
+        }
+    }
+
+    #[test]
+    fn area_light_from_surface() {
+        // One unit of area 10 units away, bright enough to match a unit point light
+        let radiance = Rgb([100.0 * std::f32::consts::PI; 3]);
+        let surface = AreaLight::surface(radiance);
+        let object = Object::rectangle(Point3::new(0.0, 10.0, 0.0), Vector3::new(0.0, -1.0, 0.0), 1.0, 1.0, &surface);
+        let light = Light::Area(AreaLight::new(Arc::new(object), 4));
+        let up = UnitVector3::new_normalize(Vector3::new(0.0, 1.0, 0.0));
+
+        let sample = light.sample(Point3::origin(), up, (0.5, 0.5)).unwrap();
+        assert!((sample.color[0] - 1.0).abs() < 1e-4, "{:?}", sample.color);
+        assert!((sample.shadow_ray.at(1.0) - Point3::new(0.0, 10.0, 0.0)).magnitude() < 1e-9);
+
+        // Nothing reaches the side the light does not face
+        assert!(light.sample(Point3::new(0.0, 20.0, 0.0), up, (0.5, 0.5)).is_none());
+    }
+
+    #[test]
+    fn sphere_light_samples_the_visible_cap() {
+        // A sphere subtending sin² = 0.04 of the sky, bright enough to give a unit of light
+        let surface = AreaLight::surface(Rgb([25.0; 3]));
+        let light = Light::Area(AreaLight::new(Arc::new(Object::sphere(Point3::new(0.0, 10.0, 0.0), 2.0, &surface)), 256));
+        let up = UnitVector3::new_normalize(Vector3::new(0.0, 1.0, 0.0));
+
+        let mut total = 0.0;
+        for i in 0..256 {
+            let u = (((i % 16) as Distance + 0.5) / 16.0, ((i / 16) as Distance + 0.5) / 16.0);
+            let sample = light.sample(Point3::origin(), up, u).expect("every point of the cap faces the receiver");
+            total += sample.color[0] / 256.0;
+        }
+        assert!((total - 1.0).abs() < 0.02, "{}", total);
+    }
+}
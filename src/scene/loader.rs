@@ -0,0 +1,918 @@
+use crate::algebra::{Distance, Point3, Ray, Vector3};
+use crate::scene::environment::{Environment, EnvironmentMap};
+use crate::scene::light::{Ambient, AreaLight, DirectionalLight, Falloff, Light, PointLight, Spot};
+use crate::scene::material::{Coat, Fresnel, Material};
+use crate::scene::obj;
+use crate::scene::object::Object;
+use crate::scene::pbr::Pbr;
+use crate::scene::surface::Surface;
+use crate::scene::texture::{CheckerboardTexture, Texture};
+use crate::scene::sky::Sky;
+use crate::scene::transform::Transform;
+use crate::scene::viewpoint::{Lens, ProjectionKind, Viewpoint};
+use crate::scene::{Color, ColorPart, Scene};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use toml::Spanned;
+
+/// Error raised while loading a scene file.
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        file: PathBuf,
+        source: std::io::Error,
+    },
+    Parse {
+        file: PathBuf,
+        line: Option<usize>,
+        key: Option<String>,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { file, source } => write!(f, "{}: {}", file.display(), source),
+            SceneError::Parse { file, line, key, message } => {
+                write!(f, "{}", file.display())?;
+                if let Some(line) = line {
+                    write!(f, ":{}", line)?;
+                }
+                if let Some(key) = key {
+                    write!(f, ": `{}`", key)?;
+                }
+                write!(f, ": {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Io { source, .. } => Some(source),
+            SceneError::Parse { .. } => None,
+        }
+    }
+}
+
+type Triple = [Distance; 3];
+type Rgb3 = [ColorPart; 3];
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    camera: Option<Spanned<CameraDesc>>,
+    environment: Option<Spanned<EnvironmentDesc>>,
+    ambient: Option<AmbientDesc>,
+    #[serde(default)]
+    materials: HashMap<String, MaterialDesc>,
+    #[serde(default)]
+    lights: Vec<LightDesc>,
+    #[serde(default)]
+    directional_lights: Vec<DirectionalLightDesc>,
+    #[serde(default)]
+    area_lights: Vec<Spanned<AreaLightDesc>>,
+    #[serde(default)]
+    spheres: Vec<Spanned<SphereDesc>>,
+    #[serde(default)]
+    cubes: Vec<Spanned<CubeDesc>>,
+    #[serde(default)]
+    planes: Vec<Spanned<PlaneDesc>>,
+    #[serde(default)]
+    meshes: Vec<Spanned<MeshDesc>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    position: Option<Triple>,
+    target: Option<Triple>,
+    up: Option<Triple>,
+    fov: Option<Distance>,
+    aperture: Option<Distance>,
+    focal_distance: Option<Distance>,
+    blades: Option<u32>,
+    projection: Option<ProjectionKind>,
+}
+
+/// Either a constant `color`, a gradient from `horizon` to `zenith`, an equirectangular `map`,
+/// or a daylight `sky`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentDesc {
+    color: Option<Rgb3>,
+    zenith: Option<Rgb3>,
+    horizon: Option<Rgb3>,
+    ground: Option<Rgb3>, // Below the horizon, the horizon color by default
+    map: Option<Spanned<PathBuf>>,
+    intensity: Option<ColorPart>,
+    rotation: Option<Distance>, // Degrees around the y axis, maps only
+    sky: Option<SkyDesc>,
+}
+
+/// Sun position in degrees, with the azimuth from +z towards +x.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SkyDesc {
+    elevation: Distance,
+    #[serde(default)]
+    azimuth: Distance,
+    turbidity: Option<Distance>,
+    sun: Option<ColorPart>, // Intensity of the sun light, zero for none
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AmbientDesc {
+    color: Rgb3,
+    occlusion: Option<OcclusionDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OcclusionDesc {
+    #[serde(default = "OcclusionDesc::default_samples")]
+    samples: u32,
+    distance: Distance,
+}
+
+impl OcclusionDesc {
+    fn default_samples() -> u32 {
+        16
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaterialDesc {
+    reflectivity: Option<ColorPart>,
+    emission: Option<Rgb3>,
+    refractive: Option<Distance>,
+    fresnel: Option<Fresnel>,
+    exterior: Option<Distance>,
+    absorption: Option<Rgb3>, // Per unit of distance inside
+    specular: Option<Rgb3>,
+    shininess: Option<Distance>,
+    metallic: Option<ColorPart>, // Makes the material physically based
+    roughness: Option<ColorPart>, // Of the microfacets of physically based materials, otherwise blurs reflection and refraction
+    glossy_samples: Option<Spanned<u32>>, // Not for physically based materials
+    coat: Option<CoatDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CoatDesc {
+    refractive: Option<Distance>,
+    roughness: Option<ColorPart>,
+    tint: Option<Rgb3>,
+    thickness: Option<Distance>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LightDesc {
+    position: Triple,
+    #[serde(default = "LightDesc::default_direction")]
+    direction: Triple,
+    color: Rgb3,
+    intensity: Option<ColorPart>,
+    falloff: Option<Falloff>,
+    range: Option<Distance>,
+    spot: Option<SpotDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DirectionalLightDesc {
+    direction: Triple,
+    color: Rgb3,
+    intensity: Option<ColorPart>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AreaLightDesc {
+    shape: AreaShapeDesc,
+    position: Triple,
+    #[serde(default = "LightDesc::default_direction")]
+    normal: Triple, // Side lit by rectangles and disks
+    size: Option<[Distance; 2]>, // Rectangles
+    radius: Option<Distance>, // Disks and spheres
+    color: Rgb3,
+    intensity: Option<ColorPart>,
+    samples: Option<u32>,
+}
+
+#[derive(Deserialize, Copy, Clone)]
+#[serde(rename_all = "lowercase")]
+enum AreaShapeDesc {
+    Rectangle,
+    Disk,
+    Sphere,
+}
+
+/// Cone angles in degrees from the light direction.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpotDesc {
+    inner: Distance,
+    outer: Distance,
+}
+
+impl LightDesc {
+    fn default_direction() -> Triple {
+        [0.0, -1.0, 0.0]
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SurfaceDesc {
+    color: Rgb3,
+    material: Option<Spanned<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CheckerboardDesc {
+    first: SurfaceDesc,
+    second: SurfaceDesc,
+    scale: Distance,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SphereDesc {
+    center: Triple,
+    radius: Distance,
+    surface: Option<SurfaceDesc>,
+    checkerboard: Option<CheckerboardDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CubeDesc {
+    center: Triple,
+    size: Distance,
+    surface: Option<SurfaceDesc>,
+    checkerboard: Option<CheckerboardDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PlaneDesc {
+    point: Triple,
+    normal: Triple,
+    surface: Option<SurfaceDesc>,
+    checkerboard: Option<CheckerboardDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MeshDesc {
+    file: Spanned<PathBuf>,
+    #[serde(default)]
+    position: Triple,
+    /// Rotation axis scaled by the angle in radians
+    #[serde(default)]
+    rotation: Triple,
+    #[serde(default = "MeshDesc::default_scale")]
+    scale: Triple,
+    surface: Option<SurfaceDesc>,
+    checkerboard: Option<CheckerboardDesc>,
+}
+
+impl MeshDesc {
+    fn default_scale() -> Triple {
+        [1.0, 1.0, 1.0]
+    }
+}
+
+/// Builds scene objects from a parsed file, keeping track of where errors come from.
+struct Builder<'a> {
+    file: &'a Path,
+    source: &'a str,
+    materials: HashMap<String, Material>,
+}
+
+impl<'a> Builder<'a> {
+    fn error(&self, span: Range<usize>, key: String, message: String) -> SceneError {
+        SceneError::Parse {
+            file: self.file.to_path_buf(),
+            line: Some(line_of(self.source, span.start)),
+            key: Some(key),
+            message,
+        }
+    }
+
+    fn surface(&self, desc: &SurfaceDesc, key: &str) -> Result<Surface, SceneError> {
+        let material = match &desc.material {
+            None => Material::DEFAULT,
+            Some(name) => *self.materials.get(name.get_ref()).ok_or_else(|| {
+                self.error(
+                    name.span(),
+                    format!("{}.material", key),
+                    format!("unknown material \"{}\"", name.get_ref()),
+                )
+            })?,
+        };
+
+        Ok(Surface::new(Color::from(desc.color), material))
+    }
+
+    /// The camera, which must look somewhere with an up direction across the view.
+    fn viewpoint(&self, camera: &Spanned<CameraDesc>) -> Result<Viewpoint, SceneError> {
+        let desc = camera.get_ref();
+        let default = Viewpoint::DEFAULT;
+        let viewpoint = Viewpoint::new(
+            desc.position.map(Point3::from).unwrap_or(default.position),
+            desc.target.map(Point3::from).unwrap_or(default.target),
+            desc.up.map(Vector3::from).unwrap_or(default.up),
+            desc.fov.unwrap_or(default.fov),
+        ).with_lens(Lens {
+            aperture: desc.aperture.unwrap_or(default.lens.aperture),
+            focal_distance: desc.focal_distance.or(default.lens.focal_distance),
+            blades: desc.blades.unwrap_or(default.lens.blades),
+        }).with_projection(desc.projection.unwrap_or(default.projection));
+
+        viewpoint.check().map_err(|message| self.error(camera.span(), "camera.target".to_string(), message))?;
+        Ok(viewpoint)
+    }
+
+    fn environment(&self, environment: &Spanned<EnvironmentDesc>) -> Result<Environment, SceneError> {
+        let desc = environment.get_ref();
+        let intensity = desc.intensity.unwrap_or(1.0);
+        let scaled = |color: Rgb3| Color::from(color.map(|c| c * intensity));
+
+        match (desc.color, desc.zenith, desc.horizon, &desc.map, &desc.sky) {
+            (Some(color), None, None, None, None) => Ok(Environment::Color(scaled(color))),
+            (None, Some(zenith), Some(horizon), None, None) => Ok(Environment::Gradient {
+                zenith: scaled(zenith),
+                horizon: scaled(horizon),
+                ground: scaled(desc.ground.unwrap_or(horizon)),
+            }),
+            (None, None, None, Some(map), None) => {
+                // Maps are relative to the scene file
+                let path = self.file.parent().unwrap_or(Path::new("")).join(map.get_ref());
+                let loaded = EnvironmentMap::load(&path, intensity, desc.rotation.unwrap_or(0.0))
+                    .map_err(|e| self.error(map.span(), "environment.map".to_string(), e.to_string()))?;
+                Ok(Environment::Map(Arc::new(loaded)))
+            }
+            (None, None, None, None, Some(sky)) => Ok(Environment::Sky(
+                Sky::new(sky.elevation, sky.azimuth, sky.turbidity.unwrap_or(3.0)).with_intensity(intensity),
+            )),
+            _ => Err(self.error(
+                environment.span(),
+                "environment".to_string(),
+                "expected exactly one of `color`, `zenith` and `horizon`, `map`, or `sky`".to_string(),
+            )),
+        }
+    }
+
+    fn texture(
+        &self,
+        surface: &Option<SurfaceDesc>,
+        checkerboard: &Option<CheckerboardDesc>,
+        key: &str,
+        span: Range<usize>,
+    ) -> Result<Box<dyn Texture>, SceneError> {
+        match (surface, checkerboard) {
+            (Some(surface), None) => Ok(Box::new(self.surface(surface, &format!("{}.surface", key))?)),
+            (None, Some(checkerboard)) => {
+                let key = format!("{}.checkerboard", key);
+                Ok(Box::new(CheckerboardTexture::new(
+                    self.surface(&checkerboard.first, &format!("{}.first", key))?,
+                    self.surface(&checkerboard.second, &format!("{}.second", key))?,
+                    checkerboard.scale,
+                )))
+            }
+            _ => Err(self.error(
+                span,
+                key.to_string(),
+                "expected exactly one of `surface` or `checkerboard`".to_string(),
+            )),
+        }
+    }
+}
+
+/// Formats a deserialization path as a dotted key, hiding the wrappers used by `Spanned`.
+fn key_of(path: &serde_path_to_error::Path) -> Option<String> {
+    use serde_path_to_error::Segment;
+
+    let mut key = String::new();
+    for segment in path.iter() {
+        match segment {
+            Segment::Seq { index } => key.push_str(&format!("[{}]", index)),
+            Segment::Map { key: name } if name.starts_with("$__serde_spanned") => {}
+            Segment::Map { key: name } | Segment::Enum { variant: name } => {
+                if !key.is_empty() {
+                    key.push('.');
+                }
+                key.push_str(name);
+            }
+            Segment::Unknown => {}
+        }
+    }
+
+    if key.is_empty() { None } else { Some(key) }
+}
+
+fn line_of(source: &str, offset: usize) -> usize {
+    source[..offset.min(source.len())].matches('\n').count() + 1
+}
+
+fn material(desc: &MaterialDesc) -> Material {
+    let default = Material::DEFAULT;
+    Material {
+        reflectivity: desc.reflectivity.unwrap_or(default.reflectivity),
+        emission: desc.emission.map(Color::from).unwrap_or(default.emission),
+        refractive: desc.refractive.unwrap_or(default.refractive),
+        fresnel: desc.fresnel.unwrap_or(default.fresnel),
+        exterior: desc.exterior.unwrap_or(default.exterior),
+        absorption: desc.absorption.map(Color::from).unwrap_or(default.absorption),
+        specular: desc.specular.map(Color::from).unwrap_or(default.specular),
+        shininess: desc.shininess.unwrap_or(default.shininess),
+        pbr: desc.metallic.map(|metallic| Pbr::new(metallic, desc.roughness.unwrap_or(0.5))),
+        roughness: match desc.metallic {
+            Some(_) => default.roughness,
+            None => desc.roughness.unwrap_or(default.roughness),
+        },
+        glossy_samples: desc.glossy_samples.as_ref().map_or(default.glossy_samples, |s| *s.get_ref()),
+        coat: desc.coat.as_ref().map(coat),
+    }
+}
+
+fn coat(desc: &CoatDesc) -> Coat {
+    let default = Coat::CLEAR;
+    Coat {
+        refractive: desc.refractive.unwrap_or(default.refractive),
+        roughness: desc.roughness.unwrap_or(default.roughness),
+        tint: desc.tint.map(Color::from).unwrap_or(default.tint),
+        thickness: desc.thickness.unwrap_or(default.thickness),
+    }
+}
+
+/// Parses a scene description in TOML format. `file` is only used for error reporting.
+pub fn parse(file: &Path, source: &str) -> Result<Scene, SceneError> {
+    let deserializer = toml::Deserializer::new(source);
+    let parsed: SceneFile = serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let key = key_of(e.path());
+        let inner = e.into_inner();
+        SceneError::Parse {
+            file: file.to_path_buf(),
+            line: inner.span().map(|span| line_of(source, span.start)),
+            key,
+            message: inner.message().to_string(),
+        }
+    })?;
+
+    let mut builder = Builder {
+        file,
+        source,
+        materials: HashMap::new(),
+    };
+    for (name, desc) in parsed.materials.iter() {
+        // `roughness` is that of the microfacets of physically based materials, which are not
+        // blurred by the Whitted tracer
+        if let (Some(_), Some(samples)) = (desc.metallic, &desc.glossy_samples) {
+            return Err(builder.error(
+                samples.span(),
+                format!("materials.{}.glossy_samples", name),
+                "`glossy_samples` cannot be set on `metallic` materials".to_string(),
+            ));
+        }
+        builder.materials.insert(name.clone(), material(desc));
+    }
+
+    let mut scene = Scene::new();
+
+    if let Some(camera) = &parsed.camera {
+        scene.set_viewpoint(builder.viewpoint(camera)?);
+    }
+
+    if let Some(environment) = &parsed.environment {
+        scene.set_environment(builder.environment(environment)?);
+
+        // The sun of a sky is lit with the same colors
+        if let (Environment::Sky(sky), Some(desc)) = (scene.environment(), &environment.get_ref().sky) {
+            let intensity = desc.sun.unwrap_or(1.0);
+            if let Some(sun) = sky.sun_light(intensity).filter(|_| intensity > 0.0) {
+                scene.add_light(Light::Directional(sun));
+            }
+        }
+    }
+
+    if let Some(ambient) = &parsed.ambient {
+        let mut added = Ambient::new(Color::from(ambient.color));
+        if let Some(occlusion) = &ambient.occlusion {
+            added = added.with_occlusion(occlusion.samples, occlusion.distance);
+        }
+        scene.set_ambient(added);
+    }
+
+    for light in parsed.lights.iter() {
+        let mut added = PointLight::new(
+            Ray::normalized(Point3::from(light.position), Vector3::from(light.direction)),
+            Color::from(light.color),
+        ).with_falloff(light.intensity.unwrap_or(1.0), light.falloff.unwrap_or(Falloff::None));
+        if let Some(range) = light.range {
+            added = added.with_range(range);
+        }
+        if let Some(spot) = &light.spot {
+            added = added.with_spot(Spot::new(spot.inner, spot.outer));
+        }
+        scene.add_light(Light::Point(added));
+    }
+
+    for (i, light) in parsed.area_lights.iter().enumerate() {
+        let key = format!("area_lights[{}]", i);
+        let desc = light.get_ref();
+        let missing = |field: &str| builder.error(light.span(), key.clone(), format!("missing field `{}`", field));
+
+        let intensity = desc.intensity.unwrap_or(1.0);
+        let radiance = Color::from(desc.color.map(|c| c * intensity));
+        let surface = AreaLight::surface(radiance);
+        let (position, normal) = (Point3::from(desc.position), Vector3::from(desc.normal));
+
+        let object = match desc.shape {
+            AreaShapeDesc::Rectangle => {
+                let [width, height] = desc.size.ok_or_else(|| missing("size"))?;
+                Object::rectangle(position, normal, width, height, &surface)
+            }
+            AreaShapeDesc::Disk => Object::disk(position, normal, desc.radius.ok_or_else(|| missing("radius"))?, &surface),
+            AreaShapeDesc::Sphere => Object::sphere(position, desc.radius.ok_or_else(|| missing("radius"))?, &surface),
+        };
+        scene.add_light(Light::Area(AreaLight::new(Arc::new(object), desc.samples.unwrap_or(AreaLight::DEFAULT_SAMPLES))));
+    }
+
+    for light in parsed.directional_lights.iter() {
+        scene.add_light(Light::Directional(
+            DirectionalLight::new(Vector3::from(light.direction), Color::from(light.color))
+                .with_intensity(light.intensity.unwrap_or(1.0)),
+        ));
+    }
+
+    for (i, sphere) in parsed.spheres.iter().enumerate() {
+        let key = format!("spheres[{}]", i);
+        let desc = sphere.get_ref();
+        let texture = builder.texture(&desc.surface, &desc.checkerboard, &key, sphere.span())?;
+        scene.add(Object::sphere(Point3::from(desc.center), desc.radius, texture.as_ref()));
+    }
+
+    for (i, cube) in parsed.cubes.iter().enumerate() {
+        let key = format!("cubes[{}]", i);
+        let desc = cube.get_ref();
+        let texture = builder.texture(&desc.surface, &desc.checkerboard, &key, cube.span())?;
+        scene.add(Object::cube(Point3::from(desc.center), desc.size, texture.as_ref()));
+    }
+
+    for (i, plane) in parsed.planes.iter().enumerate() {
+        let key = format!("planes[{}]", i);
+        let desc = plane.get_ref();
+        let texture = builder.texture(&desc.surface, &desc.checkerboard, &key, plane.span())?;
+        scene.add(Object::plane(Point3::from(desc.point), Vector3::from(desc.normal), texture.as_ref()));
+    }
+
+    for (i, mesh) in parsed.meshes.iter().enumerate() {
+        let key = format!("meshes[{}]", i);
+        let desc = mesh.get_ref();
+        let texture = builder.texture(&desc.surface, &desc.checkerboard, &key, mesh.span())?;
+        let transform = Transform::new(Vector3::from(desc.position), Vector3::from(desc.rotation), Vector3::from(desc.scale));
+
+        // Mesh files are relative to the scene file
+        let path = file.parent().unwrap_or(Path::new("")).join(desc.file.get_ref());
+        let object = obj::load_object(&path, transform, texture.as_ref())
+            .map_err(|e| builder.error(desc.file.span(), format!("{}.file", key), e.to_string()))?;
+        scene.add(object);
+    }
+
+    Ok(scene)
+}
+
+/// Loads a scene description from a TOML file.
+pub fn load(file: &Path) -> Result<Scene, SceneError> {
+    let source = std::fs::read_to_string(file).map_err(|source| SceneError::Io {
+        file: file.to_path_buf(),
+        source,
+    })?;
+
+    parse(file, &source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::UnitVector3;
+    use crate::scene::light::Occlusion;
+    use image::Rgb;
+
+    fn parse_str(source: &str) -> Result<Scene, SceneError> {
+        parse(Path::new("test.toml"), source)
+    }
+
+    #[test]
+    fn parses_objects_and_lights() {
+        let scene = parse_str(r#"
+            [materials.mirror]
+            reflectivity = 0.7
+
+            [[lights]]
+            position = [0.0, 100.0, 200.0]
+            color = [1.0, 1.0, 1.0]
+
+            [[spheres]]
+            center = [0.0, 0.0, 100.0]
+            radius = 20.0
+            surface = { color = [1.0, 0.0, 0.0], material = "mirror" }
+
+            [[planes]]
+            point = [0.0, -100.0, 0.0]
+            normal = [0.0, 1.0, 0.0]
+            checkerboard = { scale = 0.01, first = { color = [1.0, 1.0, 1.0] }, second = { color = [0.0, 1.0, 0.0] } }
+        "#).unwrap();
+
+        assert_eq!(scene.iter_lights().count(), 1);
+        assert_eq!(scene.iter_objects().count(), 2);
+    }
+
+    #[test]
+    fn reports_line_and_key() {
+        let error = parse_str("[[spheres]]\ncenter = [0.0, 0.0, 0.0]\nradius = \"big\"\n").err().unwrap();
+        match error {
+            SceneError::Parse { line, key, .. } => {
+                assert_eq!(line, Some(3));
+                assert_eq!(key.as_deref(), Some("spheres[0].radius"));
+            }
+            _ => panic!("expected parse error"),
+        }
+    }
+
+    #[test]
+    fn reports_unknown_material() {
+        let error = parse_str("[[cubes]]\ncenter = [0.0, 0.0, 0.0]\nsize = 1.0\nsurface = { color = [1.0, 1.0, 1.0], material = \"gold\" }\n").err().unwrap();
+        match error {
+            SceneError::Parse { line, key, .. } => {
+                assert_eq!(line, Some(4));
+                assert_eq!(key.as_deref(), Some("cubes[0].surface.material"));
+            }
+            _ => panic!("expected parse error"),
+        }
+    }
+
+    #[test]
+    fn parses_camera() {
+        let scene = parse_str("[camera]\nposition = [0.0, 10.0, -50.0]\ntarget = [0.0, 10.0, 0.0]\nfov = 40.0\n").unwrap();
+        let viewpoint = scene.viewpoint();
+        assert_eq!(viewpoint.position, Point3::new(0.0, 10.0, -50.0));
+        assert_eq!(viewpoint.direction(), Vector3::new(0.0, 0.0, 50.0));
+        assert_eq!(viewpoint.fov, 40.0);
+    }
+
+    #[test]
+    fn rejects_camera_without_direction() {
+        let error = parse_str("[camera]\nposition = [1.0, 2.0, 3.0]\ntarget = [1.0, 2.0, 3.0]\n").err().unwrap();
+        match error {
+            SceneError::Parse { line, key, .. } => {
+                assert_eq!(line, Some(1));
+                assert_eq!(key.as_deref(), Some("camera.target"));
+            }
+            _ => panic!("expected parse error"),
+        }
+
+        // Looking along `up` is fine, the camera picks a horizontal direction of its own
+        assert!(parse_str("[camera]\nposition = [0.0, 10.0, 0.0]\ntarget = [0.0, 0.0, 0.0]\n").is_ok());
+    }
+
+    #[test]
+    fn parses_lens_and_projection() {
+        let scene = parse_str("[camera]\naperture = 0.5\nfocal_distance = 80.0\nblades = 6\nprojection = \"fisheye\"\n").unwrap();
+        let viewpoint = scene.viewpoint();
+        assert_eq!(viewpoint.projection, ProjectionKind::Fisheye);
+        assert_eq!(viewpoint.lens.aperture, 0.5);
+        assert_eq!(viewpoint.focal_distance(), 80.0);
+        assert_eq!(viewpoint.lens.blades, 6);
+    }
+
+    #[test]
+    fn rejects_unknown_projection() {
+        let error = parse_str("[camera]\nfov = 60.0\nprojection = \"spherical\"\n").err().unwrap();
+        match error {
+            SceneError::Parse { line, key, .. } => {
+                assert_eq!(line, Some(3));
+                assert_eq!(key.as_deref(), Some("camera.projection"));
+            }
+            _ => panic!("expected parse error"),
+        }
+    }
+
+    /// Line and key of the error in a scene that does not parse.
+    fn parse_error(source: &str) -> (Option<usize>, Option<String>) {
+        match parse_str(source).err().unwrap() {
+            SceneError::Parse { line, key, .. } => (line, key),
+            _ => panic!("expected parse error"),
+        }
+    }
+
+    /// Material `m` defined by `fields`, starting on the second line, as seen on a sphere.
+    fn parse_material(fields: &str) -> Result<Material, SceneError> {
+        let scene = parse_str(&format!(
+            "[materials.m]\n{}\n[[spheres]]\ncenter = [0.0, 0.0, 0.0]\nradius = 1.0\nsurface = {{ color = [1.0, 1.0, 1.0], material = \"m\" }}\n",
+            fields
+        ))?;
+        Ok(scene.iter_objects().next().unwrap().texture.surface_at((0.0, 0.0)).material)
+    }
+
+    /// Values of the wrong kind are reported on their own line, under their key.
+    #[test]
+    fn reports_line_and_key_of_bad_values() {
+        let cases = [
+            ("[[lights]]\nposition = [0.0, 10.0, 0.0]\ncolor = [1.0, 1.0, 1.0]\nfalloff = \"cubic\"\n", 4, "lights[0].falloff"),
+            ("[[lights]]\nposition = [0.0, 10.0, 0.0]\ncolor = [1.0, 1.0, 1.0]\nspot = { inner = 15.0 }\n", 4, "lights[0].spot"),
+            ("[[directional_lights]]\ndirection = [0.0, -1.0, 0.0]\ncolor = [1.0, 1.0, 1.0]\nposition = [0.0, 0.0, 0.0]\n", 4, "directional_lights[0].position"),
+            ("[materials.glow]\nemission = [2.0, 2.0]\n", 2, "materials.glow.emission"),
+            ("[environment]\nsky = { azimuth = 90.0 }\n", 2, "environment.sky"),
+            ("[ambient]\ncolor = [0.1, 0.1, 0.1]\nocclusion = { samples = 8 }\n", 3, "ambient.occlusion"),
+            ("[materials.m]\nfresnel = \"fast\"\n", 2, "materials.m.fresnel"),
+            ("[materials.m]\nabsorption = 0.5\n", 2, "materials.m.absorption"),
+            ("[materials.m]\nshininess = \"high\"\n", 2, "materials.m.shininess"),
+            ("[materials.m]\nmetallic = true\n", 2, "materials.m.metallic"),
+            ("[materials.m]\nglossy_samples = -4\n", 2, "materials.m.glossy_samples"),
+            ("[materials.m]\ncoat = { ior = 1.5 }\n", 2, "materials.m.coat.ior"),
+        ];
+        for (source, line, key) in cases {
+            assert_eq!(parse_error(source), (Some(line), Some(key.to_string())), "{}", source);
+        }
+    }
+
+    #[test]
+    fn parses_point_light_falloff() {
+        let scene = parse_str("[[lights]]\nposition = [0.0, 10.0, 0.0]\ncolor = [1.0, 1.0, 1.0]\nintensity = 50.0\nfalloff = \"inverse-square\"\nrange = 30.0\n").unwrap();
+        match scene.iter_lights().next() {
+            Some(Light::Point(light)) => {
+                assert_eq!(light.intensity, 50.0);
+                assert_eq!(light.falloff, Falloff::InverseSquare);
+                assert_eq!(light.range, Some(30.0));
+            }
+            other => panic!("expected a point light, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_spotlight() {
+        let scene = parse_str("[[lights]]\nposition = [0.0, 10.0, 0.0]\ndirection = [0.0, -1.0, 0.0]\ncolor = [1.0, 1.0, 1.0]\nspot = { inner = 15.0, outer = 30.0 }\n").unwrap();
+        match scene.iter_lights().next() {
+            Some(Light::Point(light)) => {
+                assert_eq!(light.spot, Some(Spot::new(15.0, 30.0)));
+                assert_eq!(light.ray.direction, Vector3::new(0.0, -1.0, 0.0));
+            }
+            other => panic!("expected a point light, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_directional_light() {
+        let scene = parse_str("[[directional_lights]]\ndirection = [0.0, -2.0, 0.0]\ncolor = [1.0, 0.9, 0.8]\nintensity = 2.0\n").unwrap();
+        match scene.iter_lights().next() {
+            Some(Light::Directional(light)) => {
+                assert_eq!(light.direction.into_inner(), Vector3::new(0.0, -1.0, 0.0));
+                assert_eq!(light.intensity, 2.0);
+            }
+            other => panic!("expected a directional light, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_area_lights() {
+        let scene = parse_str("[[area_lights]]\nshape = \"rectangle\"\nposition = [0.0, 10.0, 0.0]\nnormal = [0.0, -1.0, 0.0]\nsize = [2.0, 1.0]\ncolor = [1.0, 1.0, 1.0]\nintensity = 3.0\nsamples = 9\n\n[[area_lights]]\nshape = \"disk\"\nposition = [0.0, 10.0, 0.0]\nradius = 1.0\ncolor = [1.0, 1.0, 1.0]\n\n[[area_lights]]\nshape = \"sphere\"\nposition = [0.0, 10.0, 0.0]\nradius = 1.0\ncolor = [1.0, 1.0, 1.0]\n").unwrap();
+        let lights: Vec<_> = scene.iter_lights().collect();
+        assert_eq!(lights.len(), 3);
+        assert_eq!(lights[0].sample_count(), 9);
+        assert_eq!(lights[1].sample_count(), AreaLight::DEFAULT_SAMPLES);
+
+        let up = UnitVector3::new_normalize(Vector3::new(0.0, 1.0, 0.0));
+        for light in lights {
+            match light {
+                Light::Area(area) => assert!(area.object.texture.emits_light()),
+                other => panic!("expected an area light, got {:?}", other),
+            }
+            assert!(light.sample(Point3::origin(), up, (0.5, 0.5)).is_some());
+        }
+    }
+
+    #[test]
+    fn rejects_rectangle_light_without_size() {
+        let error = parse_error("[[area_lights]]\nshape = \"rectangle\"\nposition = [0.0, 10.0, 0.0]\ncolor = [1.0, 1.0, 1.0]\n");
+        assert_eq!(error, (Some(1), Some("area_lights[0]".to_string())));
+    }
+
+    #[test]
+    fn parses_emissive_material() {
+        let scene = parse_str("[materials.glow]\nemission = [2.0, 2.0, 2.0]\n\n[[spheres]]\ncenter = [0.0, 0.0, 0.0]\nradius = 1.0\nsurface = { color = [0.0, 0.0, 0.0], material = \"glow\" }\n").unwrap();
+        assert!(scene.iter_objects().next().unwrap().texture.emits_light());
+        assert_eq!(scene.iter_lights().count(), 0);
+    }
+
+    #[test]
+    fn parses_environment_color_and_gradient() {
+        let scene = parse_str("[environment]\ncolor = [0.5, 0.25, 1.0]\nintensity = 2.0\n").unwrap();
+        assert_eq!(scene.environment().radiance(&Vector3::new(0.0, 1.0, 0.0)), Rgb([1.0, 0.5, 2.0]));
+
+        let scene = parse_str("[environment]\nzenith = [0.0, 0.0, 1.0]\nhorizon = [1.0, 1.0, 1.0]\n").unwrap();
+        match scene.environment() {
+            Environment::Gradient { zenith, horizon, ground } => {
+                assert_eq!(*zenith, Rgb([0.0, 0.0, 1.0]));
+                assert_eq!(*ground, *horizon);
+            }
+            _ => panic!("expected a gradient"),
+        }
+    }
+
+    #[test]
+    fn parses_environment_map() {
+        let path = std::env::temp_dir().join("rustray_parses_environment_map.png");
+        image::RgbImage::from_pixel(4, 2, Rgb([255, 255, 255])).save(&path).unwrap();
+        let scene = parse_str(&format!("[environment]\nmap = {:?}\nintensity = 0.5\nrotation = 90.0\n", path));
+        std::fs::remove_file(&path).unwrap();
+
+        let radiance = scene.unwrap().environment().radiance(&Vector3::new(1.0, 0.0, 0.0));
+        assert!((radiance[0] - 0.5).abs() < 1e-6, "{:?}", radiance);
+    }
+
+    #[test]
+    fn rejects_environment_with_color_and_gradient() {
+        let error = parse_error("[environment]\ncolor = [1.0, 1.0, 1.0]\nzenith = [0.0, 0.0, 1.0]\nhorizon = [1.0, 1.0, 1.0]\n");
+        assert_eq!(error, (Some(1), Some("environment".to_string())));
+    }
+
+    #[test]
+    fn rejects_missing_environment_map() {
+        let error = parse_error("[environment]\nmap = \"no_such_map.hdr\"\n");
+        assert_eq!(error, (Some(2), Some("environment.map".to_string())));
+    }
+
+    #[test]
+    fn parses_sky_with_sun() {
+        let scene = parse_str("[environment]\nsky = { elevation = 30.0, azimuth = 90.0, turbidity = 4.0, sun = 2.0 }\n").unwrap();
+        assert!(matches!(scene.environment(), Environment::Sky(_)));
+        match scene.iter_lights().next() {
+            Some(Light::Directional(sun)) => {
+                let towards = Vector3::new(30.0f64.to_radians().cos(), 30.0f64.to_radians().sin(), 0.0);
+                assert!((sun.direction.into_inner() + towards).magnitude() < 1e-9, "{:?}", sun.direction);
+                assert_eq!(sun.intensity, 2.0);
+            }
+            other => panic!("expected a directional light, got {:?}", other),
+        }
+
+        let scene = parse_str("[environment]\nsky = { elevation = 30.0, sun = 0.0 }\n").unwrap();
+        assert_eq!(scene.iter_lights().count(), 0);
+
+        // A sun below the horizon would shine up from under the ground
+        let scene = parse_str("[environment]\nsky = { elevation = -5.0 }\n").unwrap();
+        assert_eq!(scene.iter_lights().count(), 0);
+    }
+
+    #[test]
+    fn parses_ambient_with_occlusion() {
+        let scene = parse_str("[ambient]\ncolor = [0.1, 0.2, 0.3]\n").unwrap();
+        assert_eq!(scene.ambient().color, Rgb([0.1, 0.2, 0.3]));
+        assert_eq!(scene.ambient().occlusion, None);
+
+        let scene = parse_str("[ambient]\ncolor = [0.1, 0.1, 0.1]\nocclusion = { distance = 5.0 }\n").unwrap();
+        assert_eq!(scene.ambient().occlusion, Some(Occlusion { samples: 16, distance: 5.0 }));
+    }
+
+    #[test]
+    fn parses_metallic_roughness() {
+        let material = parse_material("metallic = 1.0\nroughness = 0.3\n").unwrap();
+        assert_eq!(material.pbr, Some(Pbr::new(1.0, 0.3)));
+        assert_eq!(material.roughness, Material::DEFAULT.roughness);
+        assert_eq!(parse_material("metallic = 0.0\n").unwrap().pbr, Some(Pbr::new(0.0, 0.5)));
+        assert_eq!(parse_material("").unwrap().pbr, None);
+    }
+
+    #[test]
+    fn rejects_glossy_samples_on_metallic_material() {
+        let error = parse_error("[materials.m]\nmetallic = 1.0\nroughness = 0.3\nglossy_samples = 8\n");
+        assert_eq!(error, (Some(4), Some("materials.m.glossy_samples".to_string())));
+    }
+
+    #[test]
+    fn parses_glossy_roughness() {
+        let material = parse_material("reflectivity = 0.8\nroughness = 0.2\nglossy_samples = 32\n").unwrap();
+        assert_eq!(material.roughness, 0.2);
+        assert_eq!(material.glossy_samples, 32);
+        assert_eq!(material.pbr, None);
+    }
+
+    #[test]
+    fn parses_empty_coat_as_clear() {
+        assert_eq!(parse_material("coat = {}\n").unwrap().coat, Some(Coat::CLEAR));
+        assert_eq!(parse_material("").unwrap().coat, None);
+    }
+}
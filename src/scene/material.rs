@@ -1,28 +1,183 @@
 use crate::scene::geometry::HitResult;
-use crate::algebra::{Distance, DistanceConstants, Ray};
-use crate::scene::ray::RayCaster;
+use crate::algebra::{stratum, Distance, DistanceConstants, Ray, UnitVector3, Vector3, VectorOps};
+use crate::scene::pbr::Pbr;
+use crate::scene::ray::{RayCaster, MAX_DEPTH};
 use image::{Pixel, Rgb};
+use rand::rngs::SmallRng;
+use rand::Rng;
+use serde::Deserialize;
 use std::fmt::Debug;
 use crate::scene::{Color, ColorPart};
 
+/// How light hitting a refractive material is split between reflection and refraction.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fresnel {
+    /// Reflection follows `reflectivity` and refraction is added to the diffuse light
+    None,
+    /// Schlick's approximation of the Fresnel equations
+    Schlick,
+    /// Fresnel equations for unpolarized light
+    Exact,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct Material {
     pub reflectivity: ColorPart,   // 0 for diffuse, higher values for reflective
     pub emission: Color,  // Non-zero values make the material emissive
-    pub refractive: Distance
+    pub refractive: Distance,
+    pub fresnel: Fresnel, // Anything but None makes refractive materials clear dielectrics such as glass
+    pub exterior: Distance, // Refractive index of the medium around the object
+    pub absorption: Color, // Fraction absorbed per unit of distance inside, for tinted glass and liquids
+    pub specular: Color, // Color of highlights, black for none
+    pub shininess: Distance, // Higher values give smaller and sharper highlights
+    pub pbr: Option<Pbr>, // Replaces reflectivity and highlights with physically based reflection
+    pub roughness: ColorPart, // Blurs reflection and refraction, 0 for a perfect mirror or clear glass
+    pub glossy_samples: u32, // Rays averaged for blurred reflection and refraction in the Whitted tracer
+    pub coat: Option<Coat>, // Clear layer over the rest of the material
+}
+
+/// Clear varnish over a material, as on car paint or varnished wood. It reflects as a smooth or
+/// rough dielectric, and tints the light that goes through it to the material below and back.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Coat {
+    pub refractive: Distance,
+    pub roughness: ColorPart,
+    pub tint: Color, // Left of white light crossing a layer of unit thickness straight through
+    pub thickness: Distance,
+}
+
+/// Where a ray crosses from a medium of index `n1` into one of index `n2`.
+struct Interface {
+    n1: Distance,
+    n2: Distance,
+    normal: UnitVector3, // Facing the incoming ray
+    cos_i: Distance,
+}
+
+impl Interface {
+    fn new(ray: &Ray, hit: &HitResult, interior: Distance, exterior: Distance) -> Self {
+        let cos_i = -hit.normal.dot(&ray.direction).clamp(-1.0, 1.0);
+        if cos_i < 0.0 {
+            // Leaving the object
+            Self { n1: interior, n2: exterior, normal: -hit.normal, cos_i: -cos_i }
+        } else {
+            Self { n1: exterior, n2: interior, normal: hit.normal, cos_i }
+        }
+    }
+
+    /// Cosine of the refracted ray with the normal, None on total internal reflection.
+    fn cos_t(&self) -> Option<Distance> {
+        let eta = self.n1 / self.n2;
+        let sin_t2 = eta * eta * (1.0 - self.cos_i * self.cos_i);
+        (sin_t2 <= 1.0).then(|| (1.0 - sin_t2).sqrt())
+    }
+}
+
+impl Coat {
+    pub const CLEAR: Coat = Coat {
+        refractive: 1.5,
+        roughness: 0.0,
+        tint: Rgb([1.0, 1.0, 1.0]),
+        thickness: 1.0,
+    };
+
+    /// Reflectance at normal incidence.
+    fn f0(&self) -> ColorPart {
+        ((self.refractive - 1.0) / (self.refractive + 1.0)).powi(2) as ColorPart
+    }
+
+    /// The coat reflects as a metal of a gray base color with no light under it.
+    fn layer(&self) -> (Pbr, Color) {
+        (Pbr::new(1.0, self.roughness), Rgb([self.f0(); 3]))
+    }
+
+    /// Fraction of the light seen along `view` that is reflected by the coat rather than coming
+    /// from below it, by Schlick's approximation.
+    pub fn fresnel(&self, normal: &UnitVector3, view: &Vector3) -> ColorPart {
+        let cos = view.normalize().dot(normal).clamp(0.0, 1.0) as ColorPart;
+        self.f0() + (1.0 - self.f0()) * (1.0 - cos).powi(5)
+    }
+
+    /// Light reflected by the coat alone, as `Pbr::reflectance`.
+    pub fn reflectance(&self, normal: &UnitVector3, view: &Vector3, light: &Vector3) -> Color {
+        let (pbr, base) = self.layer();
+        pbr.reflectance(base, normal, view, light)
+    }
+
+    /// Direction reflected by the coat, as `Pbr::sample`.
+    pub fn sample(&self, normal: &UnitVector3, view: &Vector3, u: (Distance, Distance)) -> Option<(Vector3, Color)> {
+        let (pbr, base) = self.layer();
+        pbr.sample(base, normal, view, u, 0.0)
+    }
+
+    /// Fraction of the light reflected by the coat as by a mirror, as `Pbr::mirror_reflectance`.
+    pub fn mirror_reflectance(&self, normal: &UnitVector3, view: &Vector3) -> Color {
+        let (pbr, base) = self.layer();
+        pbr.mirror_reflectance(base, normal, view)
+    }
+
+    /// Fraction of the light left after going down through the coat and back up along `view`,
+    /// on a path longer than the thickness where the refracted ray is slanted.
+    pub fn transmittance(&self, normal: &UnitVector3, view: &Vector3) -> Color {
+        let cos_v = view.normalize().dot(normal).clamp(0.0, 1.0);
+        let cos_t = (1.0 - (1.0 - cos_v * cos_v) / (self.refractive * self.refractive)).max(0.0).sqrt();
+        let path = (2.0 * self.thickness / cos_t.max(Distance::EPSILON)) as ColorPart;
+        self.tint.map(|t| t.powf(path))
+    }
 }
 
 impl Material {
-    pub fn shade(&self, ray: &Ray, hit: &HitResult, color: Color, caster: &dyn RayCaster, depth: u32) -> Color {
+    pub fn shade(&self, ray: &Ray, hit: &HitResult, color: Color, caster: &dyn RayCaster, depth: u32, rng: &mut SmallRng) -> Color {
+        let transmittance = self.transmittance(ray, hit);
+        let color = self.shade_coated(ray, hit, color, caster, depth, rng);
+        color.map2(&transmittance, |c, t| c * t)
+    }
+
+    /// The rest of the material seen through the coat if there is one, with the light of the
+    /// lights and a mirror reflection from the coat on top.
+    fn shade_coated(&self, ray: &Ray, hit: &HitResult, color: Color, caster: &dyn RayCaster, depth: u32, rng: &mut SmallRng) -> Color {
+        let Some(coat) = &self.coat else {
+            return self.shade_surface(ray, hit, color, caster, depth, rng);
+        };
+        let view = -ray.direction;
+        let normal = if hit.normal.dot(&view) < 0.0 { -hit.normal } else { hit.normal };
+
+        let fresnel = coat.fresnel(&normal, &view);
+        let below = self.shade_surface(ray, hit, color, caster, depth, rng)
+            .map2(&coat.transmittance(&normal, &view), |c, t| c * t * (1.0 - fresnel));
+        let reflectance = |light: &Vector3| coat.reflectance(&normal, &view, light);
+        let mut color = caster.reflected_lightning(&hit.position, &normal, &reflectance, rng).map2(&below, |c1, c2| c1 + c2);
+
+        let mirror = coat.mirror_reflectance(&normal, &view);
+        if depth > 0 && mirror != Material::BLACK {
+            let reflected_color = caster.cast(&Self::reflected_ray(ray, hit), depth - 1, rng);
+            color = color.map2(&reflected_color.map2(&mirror, |c1, c2| c1 * c2), |c1, c2| c1 + c2);
+        }
+        color
+    }
+
+    fn shade_surface(&self, ray: &Ray, hit: &HitResult, color: Color, caster: &dyn RayCaster, depth: u32, rng: &mut SmallRng) -> Color {
         // Basic shading logic with adjustable parameters
 
+        if self.is_dielectric() {
+            return self.shade_dielectric(ray, hit, caster, depth, rng);
+        }
+        if self.pbr.is_some() {
+            return self.shade_pbr(ray, hit, color, caster, depth, rng);
+        }
+
         let mut color = if self.reflectivity < 1.0 {
-            if color == Material::BLACK {
+            if color == Material::BLACK && self.specular == Material::BLACK {
                 color
             } else {
-                caster.direct_lightning(&hit.position, &hit.normal)
-                    .map(|c| c * (1.0 - self.reflectivity))
+                let direct = caster.direct_lightning(&hit.position, &hit.normal, &ray.direction, self.shininess, rng);
+                let highlights = direct.specular.map2(&self.specular, |c1, c2| c1 * c2);
+                direct.diffuse
+                    .map2(&caster.ambient_light(&hit.position, &hit.normal, rng), |c1, c2| c1 + c2)
                     .map2(&color, |c1, c2|c1 * c2)
+                    .map2(&highlights, |c1, c2| c1 + c2)
+                    .map(|c| c * (1.0 - self.reflectivity))
             }
         } else {
             Rgb([0.0, 0.0, 0.0]) // Skip diffuse lighting for fully reflective surfaces
@@ -30,14 +185,14 @@ impl Material {
         
         // Reflection
         if self.reflectivity > 0.0 && depth > 0 {
-            let reflected_color = Self::reflected_color(ray, hit, caster, depth);
+            let reflected_color = self.reflected_color(ray, hit, caster, depth, rng);
 
             color = color.map2(&reflected_color, |c1, c2|
                 c1 * (1.0 - self.reflectivity) + c2 * self.reflectivity);
         }
 
         if self.refractive > 1.0 && depth > 0 {
-            let refracted_color = Self::refracted_color(ray, hit, caster, depth, self.refractive);
+            let refracted_color = self.refracted_color(ray, hit, caster, depth, rng);
             color = color.map2(&refracted_color, |c1, c2| c1 + c2 * (1.0 - self.reflectivity));
         }
 
@@ -52,42 +207,291 @@ impl Material {
     pub const DEFAULT: Material = Material {
         emission: Rgb([0.0, 0.0, 0.0]),
         reflectivity: 0.0,
-        refractive: 1.0
+        refractive: 1.0,
+        fresnel: Fresnel::None,
+        exterior: 1.0,
+        absorption: Rgb([0.0, 0.0, 0.0]),
+        specular: Rgb([0.0, 0.0, 0.0]),
+        shininess: 32.0,
+        pbr: None,
+        roughness: 0.0,
+        glossy_samples: 8,
+        coat: None,
     };
 
     const BLACK: Color = Rgb([0.0, 0.0, 0.0]);
 
-    fn reflected_color(ray: &Ray, hit: &HitResult, caster: &dyn RayCaster, depth: u32) -> Color {
+    pub fn emits_light(&self) -> bool {
+        self.emission.0.iter().any(|c| *c > 0.0)
+    }
+
+    /// Whether light is split between reflection and refraction by the Fresnel equations,
+    /// with no diffuse light.
+    pub fn is_dielectric(&self) -> bool {
+        self.fresnel != Fresnel::None && self.refractive > 1.0
+    }
+
+    /// Fraction of the light reflected where the ray meets the surface of a dielectric,
+    /// one on total internal reflection.
+    pub fn reflectance(&self, ray: &Ray, hit: &HitResult) -> ColorPart {
+        let interface = Interface::new(ray, hit, self.refractive, self.exterior);
+        let Some(cos_t) = interface.cos_t() else {
+            return 1.0;
+        };
+        let Interface { n1, n2, cos_i, .. } = interface;
+
+        let reflectance = match self.fresnel {
+            Fresnel::None => self.reflectivity as Distance,
+            Fresnel::Schlick => {
+                let r0 = ((n1 - n2) / (n1 + n2)).powi(2);
+                // The angle on the side of the lower index
+                let cos = if n1 <= n2 { cos_i } else { cos_t };
+                r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+            }
+            Fresnel::Exact => {
+                let s = (n1 * cos_i - n2 * cos_t) / (n1 * cos_i + n2 * cos_t);
+                let p = (n1 * cos_t - n2 * cos_i) / (n1 * cos_t + n2 * cos_i);
+                (s * s + p * p) / 2.0
+            }
+        };
+        reflectance as ColorPart
+    }
+
+    /// Fraction of the light left after travelling inside the object along the ray up to the hit,
+    /// by the Beer-Lambert law. Rays arriving from outside lose nothing. Whether the ray was
+    /// inside is told by the side of the surface it hits, so absorbing objects must be closed;
+    /// the path tracer follows the medium along the path instead.
+    pub fn transmittance(&self, ray: &Ray, hit: &HitResult) -> Color {
+        if hit.normal.dot(&ray.direction) <= 0.0 {
+            return Rgb([1.0, 1.0, 1.0]);
+        }
+        self.absorbed((hit.position - ray.origin).magnitude())
+    }
+
+    /// Fraction of the light left after travelling `distance` inside the material.
+    pub fn absorbed(&self, distance: Distance) -> Color {
+        if self.absorption == Self::BLACK {
+            return Rgb([1.0, 1.0, 1.0]);
+        }
+        self.absorption.map(|a| (-a * distance as ColorPart).exp())
+    }
+
+    /// Mirror reflection of the ray at the hit, moved off the surface on the side of the ray.
+    pub fn reflected_ray(ray: &Ray, hit: &HitResult) -> Ray {
         let reflected_direction = ray.reflect(hit.normal.into_inner()).direction;
+        let side = if hit.normal.dot(&ray.direction) > 0.0 { -hit.normal } else { hit.normal };
         // Adjust along normal to avoid self-intersection
-        let reflected_ray = Ray::new(hit.position + hit.normal.into_inner() * Distance::OFF_SURFACE, reflected_direction);
-        let reflected_color = caster.cast(&reflected_ray, depth - 1);
-        reflected_color
+        Ray::new(hit.position + side.into_inner() * Distance::OFF_SURFACE, reflected_direction)
     }
 
-    fn refracted_color(ray: &Ray, hit: &HitResult, caster: &dyn RayCaster, depth: u32, refractive_index: Distance) -> Color {
-        let n1: Distance = 1.0; // Assuming ray originates in air with refractive index 1.0
-        let n2 = refractive_index;
+    /// Ray continuing through the surface, None on total internal reflection.
+    pub fn refracted_ray(&self, ray: &Ray, hit: &HitResult) -> Option<Ray> {
+        let interface = Interface::new(ray, hit, self.refractive, self.exterior);
+        let cos_t = interface.cos_t()?;
 
-        let cos_i = -hit.normal.dot(&ray.direction).max(-1.0).min(1.0);
-        let (n1, n2, normal) = if cos_i < 0.0 {
-            // Inside the material; flip normal
-            (n2, n1, -hit.normal)
-        } else {
-            (n1, n2, hit.normal)
+        let eta = interface.n1 / interface.n2;
+        let normal = interface.normal.into_inner();
+        let refracted_direction = ray.direction * eta + normal * (eta * interface.cos_i - cos_t);
+        Some(Ray::normalized(hit.position - normal * Distance::OFF_SURFACE, refracted_direction))
+    }
+
+    /// Surface of the microfacet met by a ray on a rough surface, chosen by `u` in the unit square
+    /// from the GGX distribution around the normal.
+    fn microfacet(&self, hit: &HitResult, u: (Distance, Distance)) -> HitResult {
+        let alpha2 = (self.roughness as Distance).powi(4);
+        let cos = ((1.0 - u.0) / (1.0 + (alpha2 - 1.0) * u.0)).sqrt();
+        let sin = (1.0 - cos * cos).sqrt();
+        let phi = 2.0 * Distance::PI * u.1;
+
+        let tangent = hit.normal.perpendicular();
+        let bitangent = hit.normal.cross(&tangent);
+        let normal = tangent * (sin * phi.cos()) + bitangent * (sin * phi.sin()) + hit.normal.into_inner() * cos;
+        HitResult { normal: UnitVector3::new_normalize(normal), ..*hit }
+    }
+
+    /// Reflection off a microfacet chosen by `u`, None if it would go into the surface.
+    /// Smooth surfaces reflect as a mirror.
+    pub fn glossy_reflected_ray(&self, ray: &Ray, hit: &HitResult, u: (Distance, Distance)) -> Option<Ray> {
+        if self.roughness <= 0.0 {
+            return Some(Self::reflected_ray(ray, hit));
+        }
+
+        let reflected = Self::reflected_ray(ray, &self.microfacet(hit, u));
+        let side = if hit.normal.dot(&ray.direction) > 0.0 { -hit.normal } else { hit.normal };
+        (reflected.direction.dot(&side) > 0.0)
+            .then(|| Ray::new(hit.position + side.into_inner() * Distance::OFF_SURFACE, reflected.direction))
+    }
+
+    /// Refraction through a microfacet chosen by `u`, None on total internal reflection or if it
+    /// would come back out of the surface. Smooth surfaces refract as clear glass.
+    pub fn glossy_refracted_ray(&self, ray: &Ray, hit: &HitResult, u: (Distance, Distance)) -> Option<Ray> {
+        if self.roughness <= 0.0 {
+            return self.refracted_ray(ray, hit);
+        }
+
+        let refracted = self.refracted_ray(ray, &self.microfacet(hit, u))?;
+        let side = if hit.normal.dot(&ray.direction) > 0.0 { -hit.normal } else { hit.normal };
+        (refracted.direction.dot(&side) < 0.0)
+            .then(|| Ray::new(hit.position - side.into_inner() * Distance::OFF_SURFACE, refracted.direction))
+    }
+
+    /// Average of the light brought by rays spread over the lobe of a rough surface, stratified
+    /// over the unit square. A single ray is enough for smooth surfaces, and each bounce takes a
+    /// quarter of the samples of the one before so that rays seen through rough glass do not
+    /// multiply without end.
+    fn glossy_color(&self, caster: &dyn RayCaster, depth: u32, rng: &mut SmallRng, ray_at: impl Fn((Distance, Distance)) -> Option<Ray>) -> Option<Color> {
+        let bounces = MAX_DEPTH.saturating_sub(depth).min(15);
+        let samples = if self.roughness > 0.0 { (self.glossy_samples >> (2 * bounces)).max(1) } else { 1 };
+
+        let mut sum = Material::BLACK;
+        let mut count = 0;
+        for i in 0..samples {
+            if let Some(ray) = ray_at(stratum(i, samples, rng.gen())) {
+                sum = sum.map2(&caster.cast(&ray, depth - 1, rng), |c1, c2| c1 + c2);
+                count += 1;
+            }
+        }
+        (count > 0).then(|| sum.map(|c| c / count as ColorPart))
+    }
+
+    /// Reflection and refraction weighted by the Fresnel reflectance, falling back to reflection
+    /// alone on total internal reflection.
+    fn shade_dielectric(&self, ray: &Ray, hit: &HitResult, caster: &dyn RayCaster, depth: u32, rng: &mut SmallRng) -> Color {
+        if depth == 0 {
+            return self.emission;
+        }
+
+        let reflectance = self.reflectance(ray, hit);
+        let mut color = self.reflected_color(ray, hit, caster, depth, rng).map(|c| c * reflectance);
+        if reflectance < 1.0 {
+            let refracted_color = self.refracted_color(ray, hit, caster, depth, rng);
+            color = color.map2(&refracted_color, |c1, c2| c1 + c2 * (1.0 - reflectance));
+        }
+
+        color.map2(&self.emission, |c1, c2| c1 + c2)
+    }
+
+    /// Light of the lights reflected by the BRDF, diffuse ambient light, and a mirror reflection
+    /// standing in for the reflection of everything else.
+    fn shade_pbr(&self, ray: &Ray, hit: &HitResult, base: Color, caster: &dyn RayCaster, depth: u32, rng: &mut SmallRng) -> Color {
+        let Some(pbr) = self.pbr else {
+            return self.emission;
+        };
+        let view = -ray.direction;
+        let normal = if hit.normal.dot(&view) < 0.0 { -hit.normal } else { hit.normal };
+
+        let reflectance = |light: &Vector3| pbr.reflectance(base, &normal, &view, light);
+        let ambient = caster.ambient_light(&hit.position, &normal, rng).map2(&pbr.diffuse(base), |c1, c2| c1 * c2);
+        let mut color = caster.reflected_lightning(&hit.position, &normal, &reflectance, rng).map2(&ambient, |c1, c2| c1 + c2);
+
+        let mirror = pbr.mirror_reflectance(base, &normal, &view);
+        if depth > 0 && mirror != Material::BLACK {
+            let reflected_color = self.reflected_color(ray, hit, caster, depth, rng);
+            color = color.map2(&reflected_color.map2(&mirror, |c1, c2| c1 * c2), |c1, c2| c1 + c2);
+        }
+
+        color.map2(&self.emission, |c1, c2| c1 + c2)
+    }
+
+    /// Light reflected towards the ray, as by a mirror if no sample of a rough surface stays
+    /// above it.
+    fn reflected_color(&self, ray: &Ray, hit: &HitResult, caster: &dyn RayCaster, depth: u32, rng: &mut SmallRng) -> Color {
+        self.glossy_color(caster, depth, rng, |u| self.glossy_reflected_ray(ray, hit, u))
+            .unwrap_or_else(|| caster.cast(&Self::reflected_ray(ray, hit), depth - 1, rng))
+    }
+
+    fn refracted_color(&self, ray: &Ray, hit: &HitResult, caster: &dyn RayCaster, depth: u32, rng: &mut SmallRng) -> Color {
+        self.glossy_color(caster, depth, rng, |u| self.glossy_refracted_ray(ray, hit, u))
+            .unwrap_or(Rgb([0.0, 0.0, 0.0]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::Point3;
+
+    #[test]
+    fn fresnel_reflectance_of_glass() {
+        let hit = HitResult {
+            position: Point3::origin(),
+            normal: UnitVector3::new_normalize(Vector3::new(0.0, 1.0, 0.0)),
+            coords: (0.0, 0.0),
         };
+        let glass = Material { refractive: 1.5, fresnel: Fresnel::Exact, ..Material::DEFAULT };
+        let schlick = Material { fresnel: Fresnel::Schlick, ..glass };
 
-        let eta = n1 / n2;
-        let sin_t2 = eta * eta * (1.0 - cos_i * cos_i);
+        // About 4% at normal incidence, from either side
+        for ray in [Ray::new(Point3::new(0.0, 1.0, 0.0), Vector3::new(0.0, -1.0, 0.0)), Ray::new(Point3::new(0.0, -1.0, 0.0), Vector3::new(0.0, 1.0, 0.0))] {
+            assert!((glass.reflectance(&ray, &hit) - 0.04).abs() < 1e-4);
+            assert!((schlick.reflectance(&ray, &hit) - 0.04).abs() < 1e-4);
+        }
+
+        // Total internal reflection when leaving at a grazing angle, but not when entering
+        let leaving = Ray::normalized(Point3::new(0.0, -1.0, 0.0), Vector3::new(1.0, 0.5, 0.0));
+        assert_eq!(glass.reflectance(&leaving, &hit), 1.0);
+        assert!(glass.refracted_ray(&leaving, &hit).is_none());
+        let entering = Ray::normalized(Point3::new(0.0, 1.0, 0.0), Vector3::new(1.0, -0.5, 0.0));
+        assert!(glass.reflectance(&entering, &hit) < 1.0);
+        assert!(glass.refracted_ray(&entering, &hit).unwrap().direction.y < 0.0);
+    }
+
+    #[test]
+    fn absorbs_inside_only() {
+        let hit = HitResult {
+            position: Point3::origin(),
+            normal: UnitVector3::new_normalize(Vector3::new(0.0, 1.0, 0.0)),
+            coords: (0.0, 0.0),
+        };
+        let wine = Material { refractive: 1.35, absorption: Rgb([0.1, 1.0, 1.0]), ..Material::DEFAULT };
+
+        let leaving = Ray::new(Point3::new(0.0, -2.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+        let Rgb([r, g, _]) = wine.transmittance(&leaving, &hit);
+        assert!((r - (-0.2f32).exp()).abs() < 1e-6 && (g - (-2.0f32).exp()).abs() < 1e-6);
+
+        let entering = Ray::new(Point3::new(0.0, 2.0, 0.0), Vector3::new(0.0, -1.0, 0.0));
+        assert_eq!(wine.transmittance(&entering, &hit), Rgb([1.0, 1.0, 1.0]));
+    }
+
+    #[test]
+    fn rough_reflection_spreads_above_surface() {
+        let hit = HitResult {
+            position: Point3::origin(),
+            normal: UnitVector3::new_normalize(Vector3::new(0.0, 1.0, 0.0)),
+            coords: (0.0, 0.0),
+        };
+        let ray = Ray::normalized(Point3::new(-1.0, 1.0, 0.0), Vector3::new(1.0, -1.0, 0.0));
+        let mirror = Material::reflected_ray(&ray, &hit).direction;
+        let brushed = Material { reflectivity: 1.0, roughness: 0.4, ..Material::DEFAULT };
 
-        if sin_t2 > 1.0 {
-            // Total internal reflection
-            return Rgb([0.0, 0.0, 0.0]);
+        let mut spread: Distance = 0.0;
+        for i in 0..10 {
+            for j in 0..10 {
+                let u = (i as Distance / 10.0, j as Distance / 10.0);
+                if let Some(reflected) = brushed.glossy_reflected_ray(&ray, &hit, u) {
+                    assert!(reflected.direction.y > 0.0);
+                    spread = spread.max(1.0 - reflected.direction.normalize().dot(&mirror.normalize()));
+                }
+            }
         }
+        assert!(spread > 0.01, "{}", spread);
+        assert_eq!(Material::DEFAULT.glossy_reflected_ray(&ray, &hit, (0.3, 0.7)).unwrap().direction, mirror);
+    }
+
+    /// Seen at a grazing angle, a coat reflects more and the light below crosses more of it.
+    #[test]
+    fn coat_reflects_and_tints_more_at_grazing_angles() {
+        let normal = UnitVector3::new_normalize(Vector3::new(0.0, 1.0, 0.0));
+        let amber = Coat { tint: Rgb([0.9, 0.7, 0.4]), thickness: 0.5, ..Coat::CLEAR };
+        let straight = Vector3::new(0.0, 1.0, 0.0);
+        let grazing = Vector3::new(1.0, 0.1, 0.0);
+
+        assert!((amber.fresnel(&normal, &straight) - 0.04).abs() < 1e-4);
+        assert!(amber.fresnel(&normal, &grazing) > 0.3);
 
-        let cos_t = (1.0 - sin_t2).sqrt();
-        let refracted_direction = ray.direction * eta as Distance + normal.into_inner() * (eta * cos_i - cos_t);
-        let refracted_ray = Ray::normalized(hit.position - hit.normal.into_inner() * Distance::OFF_SURFACE, refracted_direction);
-        caster.cast(&refracted_ray, depth - 1)
+        let Rgb([r, _, b]) = amber.transmittance(&normal, &straight);
+        assert!((r - 0.9).abs() < 1e-4 && (b - 0.4).abs() < 1e-4, "{} {}", r, b);
+        assert!(amber.transmittance(&normal, &grazing)[2] < b);
+        assert_eq!(Coat::CLEAR.transmittance(&normal, &grazing), Rgb([1.0, 1.0, 1.0]));
     }
 }
@@ -1,6 +1,8 @@
 use std::sync::Arc;
 use image::Rgb;
-use crate::scene::light::Light;
+use crate::scene::environment::Environment;
+use crate::scene::light::{Ambient, Light};
+use crate::scene::viewpoint::Viewpoint;
 
 pub mod geometry;
 pub mod texture;
@@ -10,20 +12,53 @@ pub mod surface;
 mod transform;
 pub mod object;
 pub mod ray;
+pub mod loader;
+pub mod obj;
+pub mod viewpoint;
+pub mod environment;
+pub mod sky;
+pub mod pbr;
 
 pub type ColorPart = f32;
 pub type Color = Rgb<ColorPart>;
 
 pub struct Scene {
     objects: Vec<Arc<object::Object>>,
-    lights: Vec<Light>
+    lights: Vec<Light>,
+    viewpoint: Viewpoint,
+    environment: Environment,
+    ambient: Ambient,
 }
 
 impl Scene {
     pub fn new() -> Scene {
-        Scene { objects: vec![], lights: vec![] }
+        Scene { objects: vec![], lights: vec![], viewpoint: Viewpoint::DEFAULT, environment: Environment::BLACK, ambient: Ambient::NONE }
+    }
+
+    pub fn set_viewpoint(&mut self, viewpoint: Viewpoint) {
+        self.viewpoint = viewpoint;
+    }
+
+    pub fn viewpoint(&self) -> &Viewpoint {
+        &self.viewpoint
     }
     
+    pub fn set_environment(&mut self, environment: Environment) {
+        self.environment = environment;
+    }
+
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+
+    pub fn set_ambient(&mut self, ambient: Ambient) {
+        self.ambient = ambient;
+    }
+
+    pub fn ambient(&self) -> &Ambient {
+        &self.ambient
+    }
+
     pub fn add(&mut self, object: object::Object) {
         self.objects.push(Arc::new(object));
     }
@@ -0,0 +1,202 @@
+use crate::algebra::{Distance, Point3, Vector3};
+use crate::scene::geometry::{TextureCoords, Triangle, TriangleMesh};
+use crate::scene::object::Object;
+use crate::scene::texture::Texture;
+use crate::scene::transform::Transform;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Error raised while loading a Wavefront OBJ file.
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        file: PathBuf,
+        source: std::io::Error,
+    },
+    Parse {
+        file: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { file, source } => write!(f, "{}: {}", file.display(), source),
+            ObjError::Parse { file, line, message } => write!(f, "{}:{}: {}", file.display(), line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+/// One `v/vt/vn` reference of a face, as zero based indices.
+struct FaceVertex {
+    position: usize,
+    coords: Option<usize>,
+    normal: Option<usize>,
+}
+
+fn parse_floats<const N: usize>(parts: &[&str]) -> Result<[Distance; N], String> {
+    if parts.len() < N {
+        return Err(format!("expected {} numbers, found {}", N, parts.len()));
+    }
+
+    let mut values = [0.0; N];
+    for (value, part) in values.iter_mut().zip(parts.iter()) {
+        *value = part.parse().map_err(|_| format!("invalid number \"{}\"", part))?;
+    }
+    Ok(values)
+}
+
+/// Resolves a one based, possibly negative (relative to the end) OBJ index.
+fn parse_index(part: &str, count: usize) -> Result<usize, String> {
+    let index: i64 = part.parse().map_err(|_| format!("invalid index \"{}\"", part))?;
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(format!("index {} out of range", index));
+    }
+    Ok(resolved as usize)
+}
+
+fn parse_face_vertex(part: &str, positions: usize, coords: usize, normals: usize) -> Result<FaceVertex, String> {
+    let mut indices = part.split('/');
+    let position = parse_index(indices.next().unwrap_or(""), positions)?;
+    let coords = match indices.next() {
+        Some("") | None => None,
+        Some(i) => Some(parse_index(i, coords)?),
+    };
+    let normal = match indices.next() {
+        Some("") | None => None,
+        Some(i) => Some(parse_index(i, normals)?),
+    };
+
+    Ok(FaceVertex { position, coords, normal })
+}
+
+/// Attributes are only used when every corner of a triangle has them.
+fn all(indices: [Option<usize>; 3]) -> Option<[usize; 3]> {
+    match indices {
+        [Some(a), Some(b), Some(c)] => Some([a, b, c]),
+        _ => None,
+    }
+}
+
+/// Parses the geometry of an OBJ file. Polygons are triangulated as fans, and
+/// statements other than `v`, `vt`, `vn` and `f` (materials, groups...) are ignored.
+pub fn parse(file: &Path, source: &str) -> Result<TriangleMesh, ObjError> {
+    let mut positions: Vec<Point3> = vec![];
+    let mut normals: Vec<Vector3> = vec![];
+    let mut coords: Vec<TextureCoords> = vec![];
+    let mut triangles: Vec<Triangle> = vec![];
+
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: String| ObjError::Parse {
+            file: file.to_path_buf(),
+            line: number + 1,
+            message,
+        };
+
+        let line = line.split('#').next().unwrap_or("");
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let Some((keyword, arguments)) = parts.split_first() else {
+            continue;
+        };
+
+        match *keyword {
+            "v" => positions.push(Point3::from(parse_floats::<3>(arguments).map_err(error)?)),
+            "vn" => normals.push(Vector3::from(parse_floats::<3>(arguments).map_err(error)?)),
+            "vt" => {
+                let [u, v] = parse_floats::<2>(arguments).map_err(error)?;
+                coords.push((u, v));
+            }
+            "f" => {
+                if arguments.len() < 3 {
+                    return Err(error(format!("face needs at least 3 vertices, found {}", arguments.len())));
+                }
+
+                let vertices = arguments
+                    .iter()
+                    .map(|part| parse_face_vertex(part, positions.len(), coords.len(), normals.len()))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(error)?;
+
+                for i in 1..vertices.len() - 1 {
+                    let corners = [&vertices[0], &vertices[i], &vertices[i + 1]];
+                    triangles.push(Triangle {
+                        positions: corners.map(|v| v.position),
+                        normals: all(corners.map(|v| v.normal)),
+                        coords: all(corners.map(|v| v.coords)),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if triangles.is_empty() {
+        return Err(ObjError::Parse {
+            file: file.to_path_buf(),
+            line: source.lines().count(),
+            message: "no faces found".to_string(),
+        });
+    }
+
+    Ok(TriangleMesh::new(positions, normals, coords, triangles))
+}
+
+/// Loads the geometry of an OBJ file as a triangle mesh.
+pub fn load(file: &Path) -> Result<TriangleMesh, ObjError> {
+    let source = std::fs::read_to_string(file).map_err(|source| ObjError::Io {
+        file: file.to_path_buf(),
+        source,
+    })?;
+
+    parse(file, &source)
+}
+
+/// Loads an OBJ file as an object placed in the world by `transform`.
+pub fn load_object(file: &Path, transform: Transform, texture: &dyn Texture) -> Result<Object, ObjError> {
+    Ok(Object::new(load(file)?, transform, texture))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_quad_as_two_triangles() {
+        let mesh = parse(Path::new("quad.obj"), "
+            # A unit quad
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vt 0 0
+            vt 1 0
+            vt 1 1
+            vt 0 1
+            vn 0 0 -1
+            f 1/1/1 2/2/1 3/3/1 -1/-1/-1
+        ").unwrap();
+
+        assert_eq!(mesh.build_stats().primitives, 2);
+    }
+
+    #[test]
+    fn reports_bad_index() {
+        match parse(Path::new("bad.obj"), "v 0 0 0\nv 1 0 0\nf 1 2 3\n") {
+            Err(ObjError::Parse { line, .. }) => assert_eq!(line, 3),
+            _ => panic!("expected parse error"),
+        }
+    }
+}
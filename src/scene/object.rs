@@ -1,11 +1,19 @@
-use crate::algebra::{Bounded, BoundingBox, Distance, Point3, UnitVector3, Vector3};
-use crate::scene::geometry::{Cube, HitResult, Plane, Sphere};
+use crate::algebra::{Bounded, BoundingBox, Distance, Point3, UnitVector3, Vector3, VectorOps};
+use crate::scene::geometry::{Cube, Disk, HitResult, Plane, Rectangle, Sphere, TextureCoords};
 use crate::algebra::Ray;
 use crate::scene::texture::Texture;
 use crate::scene::transform::Transform;
 use crate::scene::geometry::Geometry;
 use crate::scene::surface::Surface;
 
+/// Point on the surface of an object sampled as a light, in world space.
+pub struct SurfaceSample {
+    pub position: Point3,
+    pub normal: UnitVector3,
+    pub coords: TextureCoords,
+    pub area: Distance, // Surface the sample stands for, the inverse of its probability density
+}
+
 pub struct Object {
     pub geometry: Box<dyn Geometry>, // The geometry (e.g., sphere, plane)
     pub transform: Transform,             // The object's transform (world space)
@@ -44,6 +52,42 @@ impl Object {
         )
     }
     
+    /// Rectangle of `width` along x and `height` along z, before being turned to face `normal`.
+    pub fn rectangle(center: Point3, normal: Vector3, width: Distance, height: Distance, texture: &dyn Texture) -> Self {
+        Object::new(
+            Rectangle::new(),
+            Transform::new(center.coords, Transform::rotation_to(Plane::NORMAL.into_inner(), normal), Vector3::new(width / 2.0, 1.0, height / 2.0)),
+            texture
+        )
+    }
+
+    pub fn disk(center: Point3, normal: Vector3, radius: Distance, texture: &dyn Texture) -> Self {
+        Object::new(
+            Disk::new(),
+            Transform::new(center.coords, Transform::rotation_to(Plane::NORMAL.into_inner(), normal), Vector3::new(radius, 1.0, radius)),
+            texture
+        )
+    }
+
+    /// Uniformly distributed point on the part of the surface in sight of `from` for `u` in the
+    /// unit square, None if the geometry cannot be sampled.
+    pub fn sample_surface(&self, from: &Point3, u: (Distance, Distance)) -> Option<SurfaceSample> {
+        let (HitResult { position, normal, coords }, area) = self.geometry.sample_from(&self.transform.to_local_point(from), u)?;
+
+        // The transform stretches the surface around the point by the area spanned by its tangents
+        let tangent = normal.perpendicular();
+        let bitangent = normal.cross(&tangent);
+        let spanned = self.transform.apply_to_vector(&tangent).cross(&self.transform.apply_to_vector(&bitangent));
+        let outwards = if spanned.dot(&self.transform.apply_to_vector(&normal)) < 0.0 { -spanned } else { spanned };
+
+        Some(SurfaceSample {
+            position: self.transform.apply_to_point(&position),
+            normal: UnitVector3::new_normalize(outwards),
+            coords,
+            area: area * spanned.magnitude(),
+        })
+    }
+
     pub fn surface_at(&self, hr: &HitResult) -> Surface {
         self.texture.surface_at(hr.coords)
     } 
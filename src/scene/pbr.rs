@@ -0,0 +1,173 @@
+use crate::algebra::{cosine_direction, Distance, DistanceConstants, UnitVector3, Vector3, VectorOps};
+use crate::scene::{Color, ColorPart};
+use image::{Pixel, Rgb};
+
+/// Metallic-roughness parameters of glTF materials, whose base color is the surface color.
+/// Reflection follows the GGX microfacet distribution with Smith shadowing and Schlick's
+/// Fresnel, and non-metals add diffuse light under it.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Pbr {
+    pub metallic: ColorPart,  // 0 for dielectrics, 1 for metals
+    pub roughness: ColorPart, // 0 for a mirror, 1 for a matte surface
+}
+
+/// Reflectance of dielectrics at normal incidence.
+const DIELECTRIC_F0: Distance = 0.04;
+
+/// Keeps highlights of smooth surfaces from becoming infinitely small and bright.
+const MIN_ROUGHNESS: Distance = 0.03;
+
+impl Pbr {
+    pub fn new(metallic: ColorPart, roughness: ColorPart) -> Self {
+        Self {
+            metallic: metallic.clamp(0.0, 1.0),
+            roughness: roughness.clamp(0.0, 1.0),
+        }
+    }
+
+    fn alpha(&self) -> Distance {
+        (self.roughness as Distance).max(MIN_ROUGHNESS).powi(2)
+    }
+
+    /// Reflectance at normal incidence, tinted by the base color for metals.
+    fn f0(&self, base: Color) -> [Distance; 3] {
+        let metallic = self.metallic as Distance;
+        base.0.map(|c| DIELECTRIC_F0 + (c as Distance - DIELECTRIC_F0) * metallic)
+    }
+
+    fn fresnel(&self, base: Color, cos: Distance) -> [Distance; 3] {
+        self.f0(base).map(|f0| f0 + (1.0 - f0) * (1.0 - cos.clamp(0.0, 1.0)).powi(5))
+    }
+
+    /// GGX distribution of the microfacet normals.
+    fn distribution(&self, cos_h: Distance) -> Distance {
+        let alpha2 = self.alpha().powi(2);
+        let d = cos_h * cos_h * (alpha2 - 1.0) + 1.0;
+        alpha2 / (Distance::PI * d * d)
+    }
+
+    /// Smith shadowing and masking, separable form.
+    fn geometry(&self, cos_l: Distance, cos_v: Distance) -> Distance {
+        let alpha2 = self.alpha().powi(2);
+        let g1 = |cos: Distance| 2.0 * cos / (cos + (alpha2 + (1.0 - alpha2) * cos * cos).sqrt());
+        g1(cos_l) * g1(cos_v)
+    }
+
+    /// Light reflected towards `view` from light arriving from `light`, both pointing away from
+    /// the surface. It is PI times the BRDF, to multiply the light of `direct_lightning` in which
+    /// a white diffuse surface reflects everything.
+    pub fn reflectance(&self, base: Color, normal: &UnitVector3, view: &Vector3, light: &Vector3) -> Color {
+        let (light, view) = (light.normalize(), view.normalize());
+        let cos_l = light.dot(normal);
+        let cos_v = view.dot(normal);
+        if cos_l <= 0.0 || cos_v <= 0.0 {
+            return Rgb([0.0, 0.0, 0.0]);
+        }
+
+        let half = (light + view).normalize();
+        let fresnel = self.fresnel(base, view.dot(&half));
+        let specular = self.distribution(half.dot(normal)) * self.geometry(cos_l, cos_v) / (4.0 * cos_l * cos_v);
+        let diffuse = 1.0 - self.metallic as Distance;
+
+        Rgb([0, 1, 2].map(|i| {
+            let f = fresnel[i];
+            ((1.0 - f) * diffuse * base[i] as Distance + Distance::PI * f * specular) as ColorPart
+        }))
+    }
+
+    /// Chance of sampling the reflection lobe rather than the diffuse one.
+    fn specular_probability(&self, base: Color) -> Distance {
+        let specular = self.f0(base).iter().sum::<Distance>();
+        let diffuse = (1.0 - self.metallic as Distance) * base.0.iter().map(|c| *c as Distance).sum::<Distance>();
+        (specular / (specular + diffuse).max(Distance::EPSILON)).clamp(0.25, 1.0)
+    }
+
+    /// Direction of light reflected towards `view`, chosen by `u` in the unit square and `lobe`
+    /// in the unit interval, and the reflectance divided by the probability of the direction.
+    pub fn sample(&self, base: Color, normal: &UnitVector3, view: &Vector3, u: (Distance, Distance), lobe: Distance) -> Option<(Vector3, Color)> {
+        let view = view.normalize();
+        let specular_probability = self.specular_probability(base);
+
+        let light = if lobe < specular_probability {
+            // Microfacet normal from the distribution, mirroring the view
+            let alpha2 = self.alpha().powi(2);
+            let cos_h = ((1.0 - u.0) / (1.0 + (alpha2 - 1.0) * u.0)).sqrt();
+            let sin_h = (1.0 - cos_h * cos_h).sqrt();
+            let phi = 2.0 * Distance::PI * u.1;
+            let tangent = normal.perpendicular();
+            let bitangent = normal.cross(&tangent);
+            let half = tangent * (sin_h * phi.cos()) + bitangent * (sin_h * phi.sin()) + normal.into_inner() * cos_h;
+            half * (2.0 * view.dot(&half)) - view
+        } else {
+            cosine_direction(normal, u)
+        };
+
+        let cos_l = light.dot(normal);
+        if cos_l <= 0.0 {
+            return None;
+        }
+
+        // Either lobe could have given the direction
+        let half = (light + view).normalize();
+        let cos_h = half.dot(normal);
+        let specular_density = self.distribution(cos_h) * cos_h / (4.0 * view.dot(&half)).max(Distance::EPSILON);
+        let diffuse_density = cos_l / Distance::PI;
+        let density = specular_probability * specular_density + (1.0 - specular_probability) * diffuse_density;
+
+        let weight = (cos_l / (Distance::PI * density)) as ColorPart;
+        Some((light, self.reflectance(base, normal, &view, &light).map(|c| c * weight)))
+    }
+
+    /// Fraction of the light reflected as by a mirror, for tracers that follow one reflected
+    /// ray. It fades out as the surface gets rough.
+    pub fn mirror_reflectance(&self, base: Color, normal: &UnitVector3, view: &Vector3) -> Color {
+        let cos_v = view.normalize().dot(normal);
+        let smoothness = (1.0 - self.roughness as Distance).powi(2);
+        Rgb(self.fresnel(base, cos_v).map(|f| (f * smoothness) as ColorPart))
+    }
+
+    /// Fraction of ambient and environment light reflected diffusely.
+    pub fn diffuse(&self, base: Color) -> Color {
+        base.map(|c| c * (1.0 - self.metallic))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    /// Sampling favours some directions but gives the same reflected light as sampling all
+    /// directions uniformly, and no more than arrives.
+    #[test]
+    fn sampling_matches_uniform_integration() {
+        let mut rng = SmallRng::seed_from_u64(23);
+        let normal = UnitVector3::new_normalize(Vector3::new(0.0, 1.0, 0.0));
+        let view = Vector3::new(0.5, 1.0, 0.0);
+        let white = Rgb([1.0, 1.0, 1.0]);
+        let runs = 50000;
+
+        for (metallic, roughness) in [(0.0, 0.5), (1.0, 0.3), (1.0, 0.8)] {
+            let pbr = Pbr::new(metallic, roughness);
+            let sampled = (0..runs)
+                .filter_map(|_| pbr.sample(white, &normal, &view, rng.gen(), rng.gen()))
+                .map(|(_, weight)| weight[0])
+                .sum::<ColorPart>() / runs as ColorPart;
+
+            // Over the hemisphere, with the z of a uniform direction uniform itself
+            let uniform = (0..runs)
+                .map(|_| {
+                    let (z, phi) = (rng.gen::<Distance>(), 2.0 * Distance::PI * rng.gen::<Distance>());
+                    let radius = (1.0 - z * z).sqrt();
+                    let light = Vector3::new(radius * phi.cos(), z, radius * phi.sin());
+                    pbr.reflectance(white, &normal, &view, &light)[0] * (2.0 * z) as ColorPart
+                })
+                .sum::<ColorPart>() / runs as ColorPart;
+
+            // The two estimates typically differ by about 0.02 with this many runs
+            assert!((sampled - uniform).abs() < 0.08, "{} {} {} {}", metallic, roughness, sampled, uniform);
+            assert!(sampled < 1.02, "{} {} {}", metallic, roughness, sampled);
+        }
+    }
+}
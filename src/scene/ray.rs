@@ -1,8 +1,29 @@
-use crate::algebra::{Point3, Ray, UnitVector3};
+use crate::algebra::{Distance, Point3, Ray, UnitVector3, Vector3};
 use crate::scene::Color;
+use rand::rngs::SmallRng;
 
+/// Reflections and refractions followed from a camera ray before giving up.
+pub const MAX_DEPTH: u32 = 5;
+
+/// Light arriving directly from the lights at a point.
+pub struct DirectLight {
+    pub diffuse: Color,
+    pub specular: Color, // Blinn-Phong highlights, for the shininess asked for
+}
+
+/// Computes the light along rays. Random choices are drawn from `rng`, so that a render seeded
+/// the same way is the same.
 pub trait RayCaster: Sync {
-    fn cast(&self, ray: &Ray, depth: u32) -> Color;
+    fn cast(&self, ray: &Ray, depth: u32, rng: &mut SmallRng) -> Color;
+
+    /// Light arriving at a point seen along the direction `view`, with highlights as sharp as
+    /// the `shininess` exponent makes them.
+    fn direct_lightning(&self, position: &Point3, normal: &UnitVector3, view: &Vector3, shininess: Distance, rng: &mut SmallRng) -> DirectLight;
+
+    /// Light arriving at a point and reflected by a BRDF, given as the fraction of the light
+    /// from each direction that is reflected.
+    fn reflected_lightning(&self, position: &Point3, normal: &UnitVector3, reflectance: &dyn Fn(&Vector3) -> Color, rng: &mut SmallRng) -> Color;
 
-    fn direct_lightning(&self, position: &Point3, normal: &UnitVector3) -> Color;
+    /// Ambient light reaching a point, darkened where nearby objects occlude it.
+    fn ambient_light(&self, position: &Point3, normal: &UnitVector3, rng: &mut SmallRng) -> Color;
 }
\ No newline at end of file
@@ -0,0 +1,133 @@
+use crate::algebra::{Distance, DistanceConstants, Vector3};
+use crate::scene::light::DirectionalLight;
+use crate::scene::{Color, ColorPart};
+use image::{Pixel, Rgb};
+
+/// Daylight sky of the Preetham model, "A Practical Analytic Model for Daylight" (1999), lit
+/// by a sun at the given position. Turbidity goes from 2 for a clear sky to about 10 for haze.
+#[derive(Debug, Copy, Clone)]
+pub struct Sky {
+    sun: Vector3, // Unit vector towards the sun
+    turbidity: Distance,
+    intensity: ColorPart,
+    perez: [[Distance; 5]; 3], // Distribution of Y, x and y over the sky
+    zenith: [Distance; 3],     // Y, x and y at the zenith
+}
+
+/// Brings luminances in kcd/m² to the brightness of the lights of most scenes.
+const LUMINANCE_SCALE: Distance = 0.03;
+
+/// Sun illuminance of about 100 klx, in the same units as the sky.
+const SUN_ILLUMINANCE: Distance = 100.0 / Distance::PI * LUMINANCE_SCALE;
+
+/// Perez formula, how much brighter a direction is than the zenith, before normalization.
+fn perez([a, b, c, d, e]: [Distance; 5], cos_theta: Distance, gamma: Distance) -> Distance {
+    (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+}
+
+fn cubic([a, b, c, d]: [Distance; 4], x: Distance) -> Distance {
+    ((a * x + b) * x + c) * x + d
+}
+
+/// Linear sRGB from CIE xyY.
+fn rgb_from_xy_luminance(x: Distance, y: Distance, luminance: Distance) -> Color {
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+    Rgb([
+        3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+        -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+        0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+    ]
+    .map(|c| c.max(0.0) as ColorPart))
+}
+
+impl Sky {
+    /// Sun `elevation` above the horizon and `azimuth` from +z towards +x, in degrees.
+    pub fn new(elevation: Distance, azimuth: Distance, turbidity: Distance) -> Self {
+        let (elevation, azimuth) = (elevation.to_radians(), azimuth.to_radians());
+        let sun = Vector3::new(elevation.cos() * azimuth.sin(), elevation.sin(), elevation.cos() * azimuth.cos());
+        let t = turbidity.max(1.0);
+
+        let perez = [
+            [0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703],
+            [-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452],
+            [-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529],
+        ];
+
+        // Zenith values from the fits of the paper, with the sun at `theta` from the zenith
+        let theta = Distance::PI / 2.0 - elevation.max(0.0);
+        let chi = (4.0 / 9.0 - t / 120.0) * (Distance::PI - 2.0 * theta);
+        let luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let x = t * t * cubic([0.00166, -0.00375, 0.00209, 0.0], theta)
+            + t * cubic([-0.02903, 0.06377, -0.03202, 0.00394], theta)
+            + cubic([0.11693, -0.21196, 0.06052, 0.25886], theta);
+        let y = t * t * cubic([0.00275, -0.00610, 0.00317, 0.0], theta)
+            + t * cubic([-0.04214, 0.08970, -0.04153, 0.00516], theta)
+            + cubic([0.15346, -0.26756, 0.06670, 0.26688], theta);
+
+        Self {
+            sun,
+            turbidity: t,
+            intensity: 1.0,
+            perez,
+            zenith: [luminance, x, y],
+        }
+    }
+
+    pub fn with_intensity(self, intensity: ColorPart) -> Self {
+        Self { intensity, ..self }
+    }
+
+    /// Light of the sky seen in a direction. Below the horizon it is the sky at the horizon.
+    pub fn radiance(&self, direction: &Vector3) -> Color {
+        let direction = direction.normalize();
+        let cos_theta = direction.y.max(0.01);
+        let gamma = direction.dot(&self.sun).clamp(-1.0, 1.0).acos();
+        let theta_sun = self.sun.y.max(0.0).acos();
+
+        let [luminance, x, y] = [0, 1, 2].map(|i| {
+            self.zenith[i] * perez(self.perez[i], cos_theta, gamma) / perez(self.perez[i], 1.0, theta_sun)
+        });
+        rgb_from_xy_luminance(x, y, luminance * LUMINANCE_SCALE).map(|c| c * self.intensity)
+    }
+
+    /// Directional light of the sun, reddened by the air it goes through as in the appendix of
+    /// the paper, for red, green and blue wavelengths. Ozone and water vapour are left out.
+    /// None when the sun is not above the horizon.
+    pub fn sun_light(&self, intensity: ColorPart) -> Option<DirectionalLight> {
+        if self.sun.y <= 0.0 {
+            return None;
+        }
+        let theta = self.sun.y.min(1.0).acos();
+        let optical_mass = 1.0 / (theta.cos() + 0.15 * (93.885 - theta.to_degrees()).powf(-1.253));
+        let beta = 0.04608 * self.turbidity - 0.04586;
+
+        let transmittance = [0.68, 0.55, 0.44].map(|wavelength: Distance| {
+            let rayleigh = (-0.008735 * wavelength.powf(-4.08) * optical_mass).exp();
+            let aerosol = (-beta * wavelength.powf(-1.3) * optical_mass).exp();
+            (rayleigh * aerosol * SUN_ILLUMINANCE) as ColorPart
+        });
+
+        Some(DirectionalLight::new(-self.sun, Rgb(transmittance)).with_intensity(intensity * self.intensity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sky_is_blue_and_sun_is_redder_when_low() {
+        let sky = Sky::new(45.0, 0.0, 3.0);
+        let Rgb([r, _, b]) = sky.radiance(&Vector3::new(0.0, 1.0, -1.0));
+        assert!(b > r, "{} {}", r, b);
+
+        let high = sky.sun_light(1.0).unwrap().color;
+        let low = Sky::new(5.0, 0.0, 3.0).sun_light(1.0).unwrap().color;
+        assert!(low[2] / low[0] < high[2] / high[0], "{:?} {:?}", low, high);
+
+        // Below the horizon, the sun lights nothing
+        assert!(Sky::new(0.0, 0.0, 3.0).sun_light(1.0).is_none());
+        assert!(Sky::new(-10.0, 0.0, 3.0).sun_light(1.0).is_none());
+    }
+}
@@ -31,6 +31,9 @@ impl Texture for CheckerboardTexture {
         }
     }
 
+    fn emits_light(&self) -> bool {
+        self.surface1.material.emits_light() || self.surface2.material.emits_light()
+    }
 
     fn clone_box(&self) -> Box<dyn Texture> {
         return Box::new(self.clone());
@@ -7,6 +7,9 @@ pub trait Texture: Send + Sync {
     /// Returns a `Surface` at the given `TextureCoords` on the object's surface.
     fn surface_at(&self, coords: TextureCoords) -> Surface;
 
+    /// Whether any part of the texture has a material with emission.
+    fn emits_light(&self) -> bool;
+
     fn clone_box(&self) -> Box<dyn Texture>;
 }
 
@@ -15,6 +18,10 @@ impl Texture for Surface {
         *self
     }
 
+    fn emits_light(&self) -> bool {
+        self.material.emits_light()
+    }
+
     fn clone_box(&self) -> Box<dyn Texture> {
         Box::new(self.clone())
     }
@@ -37,6 +37,10 @@ impl Transform {
         self.matrix.transform_vector(v)
     }
 
+    pub fn to_local_point(&self, point: &Point3) -> Point3 {
+        self.inverse_matrix.transform_point(point)
+    }
+
     pub fn to_local_ray(&self, ray: &Ray) -> Ray {
         Ray::new(
             self.inverse_matrix.transform_point(&ray.origin),
@@ -101,4 +105,15 @@ mod tests {
         assert_eq!(local_ray.origin, Point3::new(-2.5, -2.0, -2.0));
         assert_eq!(local_ray.direction, Vector3::new(0.2886751345948129, 0.5773502691896258, 0.5773502691896258));
     }
+
+    #[test]
+    fn test_to_local_rotated() {
+        let t = Transform::new(Vector3::new(1.0, 2.0, 3.0), Vector3::new(0.0, 0.5, 0.0), Vector3::new(2.0, 2.0, 2.0));
+
+        let ray = Ray::new(Point3::new(4.0, -1.0, 2.0), Vector3::new(1.0, 2.0, -3.0));
+        let local_ray = t.to_local_ray(&ray);
+
+        assert!((t.apply_to_point(&local_ray.origin) - ray.origin).magnitude() < 1e-9);
+        assert!((t.apply_to_vector(&local_ray.direction) - ray.direction).magnitude() < 1e-9);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,83 @@
+use crate::algebra::{Distance, Point3, Vector3};
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// Where the scene is viewed from.
+#[derive(Debug, Copy, Clone)]
+pub struct Viewpoint {
+    pub position: Point3,
+    pub target: Point3,
+    pub up: Vector3,
+    pub fov: Distance, // Vertical field of view in degrees
+    pub lens: Lens,
+    pub projection: ProjectionKind,
+}
+
+/// How directions around the camera are laid out on the image.
+#[derive(ValueEnum, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectionKind {
+    /// Pinhole camera
+    Perspective,
+    /// Parallel rays, covering the area the perspective view shows at the focal distance
+    Orthographic,
+    /// Equidistant fisheye, the field of view is the diameter of the image circle
+    Fisheye,
+    /// Full 360° panorama, the field of view is not used
+    Equirectangular,
+}
+
+/// Thin lens of the camera, an aperture of zero is a pinhole camera with everything in focus.
+#[derive(Debug, Copy, Clone)]
+pub struct Lens {
+    pub aperture: Distance, // Radius of the lens
+    pub focal_distance: Option<Distance>, // Distance to the plane in focus, the target if not set
+    pub blades: u32, // Aperture blades shaping polygonal bokeh, zero for a circular aperture
+}
+
+impl Lens {
+    pub const PINHOLE: Lens = Lens {
+        aperture: 0.0,
+        focal_distance: None,
+        blades: 0,
+    };
+}
+
+impl Viewpoint {
+    pub const DEFAULT: Viewpoint = Viewpoint {
+        position: Point3::new(0.0, 0.0, 0.0),
+        target: Point3::new(0.0, 0.1, 1.0),
+        up: Vector3::new(0.0, 1.0, 0.0),
+        fov: 50.0,
+        lens: Lens::PINHOLE,
+        projection: ProjectionKind::Perspective,
+    };
+
+    pub fn new(position: Point3, target: Point3, up: Vector3, fov: Distance) -> Self {
+        Self { position, target, up, fov, lens: Lens::PINHOLE, projection: ProjectionKind::Perspective }
+    }
+
+    pub fn with_lens(self, lens: Lens) -> Self {
+        Self { lens, ..self }
+    }
+
+    pub fn with_projection(self, projection: ProjectionKind) -> Self {
+        Self { projection, ..self }
+    }
+
+    pub fn direction(&self) -> Vector3 {
+        self.target - self.position
+    }
+
+    /// Error message if there is no direction to look in, with the target at the position.
+    pub fn check(&self) -> Result<(), String> {
+        if self.direction().magnitude() <= Distance::EPSILON {
+            return Err("the target is at the camera position".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn focal_distance(&self) -> Distance {
+        self.lens.focal_distance.unwrap_or_else(|| self.direction().magnitude())
+    }
+}
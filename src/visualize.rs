@@ -81,7 +81,7 @@ impl Processor {
         }
     }
 }
-pub fn show(width: i32, height: i32, f: impl Fn(Sender<ShowMessage>) + Copy + Send + 'static) {
+pub fn show(width: i32, height: i32, f: impl Fn(Sender<ShowMessage>) + Clone + Send + 'static) {
     let app = Application::builder()
         .application_id("org.example.RealtimeRenderer")
         .flags(gio::ApplicationFlags::NON_UNIQUE)
@@ -113,6 +113,7 @@ pub fn show(width: i32, height: i32, f: impl Fn(Sender<ShowMessage>) + Copy + Se
         window.set_child(Some(&container));
         window.present();
 
+        let f = f.clone();
         glib::spawn_future_local(async move {
             let (tx, rx) = async_channel::unbounded::<ShowMessage>();
 